@@ -0,0 +1,132 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Error, Fields, Result};
+
+/// The GLSL layout rules to check a struct against.
+#[derive(Copy, Clone)]
+pub enum Layout {
+    Std140,
+    Std430,
+}
+
+impl Layout {
+    fn name(self) -> &'static str {
+        match self {
+            Layout::Std140 => "std140",
+            Layout::Std430 => "std430",
+        }
+    }
+}
+
+fn has_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().any(|attr| {
+        attr.path.is_ident("repr")
+            && attr
+                .parse_args_with(
+                    syn::punctuated::Punctuated::<syn::Ident, syn::Token![,]>::parse_terminated,
+                )
+                .map(|idents| idents.iter().any(|ident| ident == "C"))
+                .unwrap_or(false)
+    })
+}
+
+pub fn derive(input: DeriveInput, layout: Layout) -> Result<TokenStream> {
+    let layout_name = layout.name();
+    let trait_ident = format_ident!(
+        "{}",
+        match layout {
+            Layout::Std140 => "Std140",
+            Layout::Std430 => "Std430",
+        }
+    );
+    let trait_path = quote!(::favilla::layout::#trait_ident);
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            format!("`{}` cannot be derived for generic structs", trait_ident),
+        ));
+    }
+    if !has_repr_c(&input) {
+        return Err(Error::new(
+            input.ident.span(),
+            format!("`{}` requires the struct to be `#[repr(C)]`", trait_ident),
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => named.named.iter().collect::<Vec<_>>(),
+            _ => {
+                return Err(Error::new(
+                    input.ident.span(),
+                    format!(
+                        "`{}` can only be derived for structs with named fields",
+                        trait_ident
+                    ),
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                format!("`{}` can only be derived for structs", trait_ident),
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let checks = fields.iter().map(|field| {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let offset_message = format!(
+            "{} layout mismatch: field `{}` of `{}` is not at its {} offset",
+            layout_name, ident, name, layout_name
+        );
+        let size_message = format!(
+            "{} layout mismatch: the Rust size of field `{}` of `{}` differs from its {} size",
+            layout_name, ident, name, layout_name
+        );
+        quote_spanned! {ty.span()=>
+            offset = ::favilla::layout::align_to(offset, <#ty as #trait_path>::ALIGNMENT);
+            ::core::assert!(offset == ::core::mem::offset_of!(#name, #ident), #offset_message);
+            ::core::assert!(
+                ::core::mem::size_of::<#ty>() == <#ty as #trait_path>::SIZE,
+                #size_message
+            );
+            offset += <#ty as #trait_path>::SIZE;
+        }
+    });
+
+    let alignments = fields.iter().map(|field| {
+        let ty = &field.ty;
+        quote!(<#ty as #trait_path>::ALIGNMENT)
+    });
+    let alignment = match layout {
+        Layout::Std140 => quote!(::favilla::layout::std140_struct_alignment(&[#(#alignments),*])),
+        Layout::Std430 => quote!(::favilla::layout::max_alignment(&[#(#alignments),*])),
+    };
+
+    let size_message = format!(
+        "{} layout mismatch: the size of `{}` differs from its {} size; add explicit `Padding` at the end",
+        layout_name, name, layout_name
+    );
+
+    Ok(quote! {
+        unsafe impl #trait_path for #name {
+            const ALIGNMENT: usize = #alignment;
+            const SIZE: usize = ::core::mem::size_of::<Self>();
+        }
+
+        const _: () = {
+            let mut offset: usize = 0;
+            #(#checks)*
+            ::core::assert!(
+                ::favilla::layout::align_to(offset, <#name as #trait_path>::ALIGNMENT)
+                    == ::core::mem::size_of::<#name>(),
+                #size_message
+            );
+        };
+    })
+}
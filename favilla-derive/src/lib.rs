@@ -0,0 +1,50 @@
+#![deny(rust_2018_idioms)]
+#![deny(clippy::all)]
+
+//! Derive macros for `favilla`. Use them through `favilla`'s `derive` feature.
+
+mod layout;
+mod vertex;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+/// Derives `favilla::vertex::VertexInput` for a struct.
+///
+/// Every field becomes one vertex attribute; its format is taken from the field type's
+/// `favilla::vertex::VertexAttribute` implementation, or from a `#[vertex(format = "...")]`
+/// attribute naming a `vk::Format` constant. Locations are assigned in field order.
+///
+/// Struct attributes:
+/// * `#[vertex(binding = N)]`: the binding index (default 0).
+/// * `#[vertex(location = N)]`: the location of the first attribute (default 0).
+/// * `#[vertex(instance)]`: use `VertexInputRate::INSTANCE` instead of `VERTEX`.
+#[proc_macro_derive(Vertex, attributes(vertex))]
+pub fn derive_vertex(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    vertex::derive(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Derives `favilla::layout::Std140` for a `#[repr(C)]` struct with named fields.
+///
+/// Every field offset and the struct size are checked against the GLSL `std140` rules at compile time.
+#[proc_macro_derive(Std140)]
+pub fn derive_std140(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    layout::derive(input, layout::Layout::Std140)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Derives `favilla::layout::Std430` for a `#[repr(C)]` struct with named fields.
+///
+/// Every field offset and the struct size are checked against the GLSL `std430` rules at compile time.
+#[proc_macro_derive(Std430)]
+pub fn derive_std430(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    layout::derive(input, layout::Layout::Std430)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
@@ -0,0 +1,160 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Error, Fields, Lit, LitStr, Meta, NestedMeta, Result};
+
+struct StructOptions {
+    binding: u32,
+    location: u32,
+    instance: bool,
+}
+
+fn vertex_attribute_metas(attrs: &[syn::Attribute]) -> Result<Vec<NestedMeta>> {
+    let mut metas = vec![];
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("vertex")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => metas.extend(list.nested),
+            meta => return Err(Error::new(meta.span(), "expected `#[vertex(...)]`")),
+        }
+    }
+    Ok(metas)
+}
+
+fn parse_u32(lit: &Lit) -> Result<u32> {
+    match lit {
+        Lit::Int(int) => int.base10_parse(),
+        _ => Err(Error::new(lit.span(), "expected an integer")),
+    }
+}
+
+fn parse_struct_options(input: &DeriveInput) -> Result<StructOptions> {
+    let mut options = StructOptions {
+        binding: 0,
+        location: 0,
+        instance: false,
+    };
+
+    for meta in vertex_attribute_metas(&input.attrs)? {
+        match meta {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("binding") => {
+                options.binding = parse_u32(&nv.lit)?;
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("location") => {
+                options.location = parse_u32(&nv.lit)?;
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("instance") => {
+                options.instance = true;
+            }
+            meta => return Err(Error::new(
+                meta.span(),
+                "unknown vertex attribute; expected `binding = N`, `location = N` or `instance`",
+            )),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_format_override(field: &syn::Field) -> Result<Option<LitStr>> {
+    let mut format = None;
+    for meta in vertex_attribute_metas(&field.attrs)? {
+        match meta {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("format") => match nv.lit {
+                Lit::Str(lit) => format = Some(lit),
+                lit => return Err(Error::new(lit.span(), "expected a format name string")),
+            },
+            meta => {
+                return Err(Error::new(
+                    meta.span(),
+                    "unknown vertex attribute; expected `format = \"...\"`",
+                ))
+            }
+        }
+    }
+    Ok(format)
+}
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let options = parse_struct_options(&input)?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "`Vertex` can only be derived for structs",
+            ))
+        }
+    };
+
+    let field_list = match fields {
+        Fields::Named(named) => named.named.iter().collect::<Vec<_>>(),
+        Fields::Unnamed(unnamed) => unnamed.unnamed.iter().collect::<Vec<_>>(),
+        Fields::Unit => vec![],
+    };
+    if field_list.is_empty() {
+        return Err(Error::new(
+            input.ident.span(),
+            "`Vertex` requires at least one field",
+        ));
+    }
+
+    let binding = options.binding;
+    let attributes = field_list
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let location = options.location + index as u32;
+            let member = match &field.ident {
+                Some(ident) => quote!(#ident),
+                None => {
+                    let index = syn::Index::from(index);
+                    quote!(#index)
+                }
+            };
+            let ty = &field.ty;
+            let format = match parse_format_override(field)? {
+                Some(name) => {
+                    let ident = format_ident!("{}", name.value(), span = name.span());
+                    quote!(::favilla::vertex::__private::vk::Format::#ident)
+                }
+                None => quote_spanned! {ty.span()=>
+                    <#ty as ::favilla::vertex::VertexAttribute>::FORMAT
+                },
+            };
+            Ok(quote! {
+                ::favilla::vertex::__private::vk::VertexInputAttributeDescription {
+                    location: #location,
+                    binding: #binding,
+                    format: #format,
+                    offset: ::core::mem::offset_of!(Self, #member) as u32,
+                }
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let input_rate = if options.instance {
+        quote!(::favilla::vertex::__private::vk::VertexInputRate::INSTANCE)
+    } else {
+        quote!(::favilla::vertex::__private::vk::VertexInputRate::VERTEX)
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::favilla::vertex::VertexInput for #name #ty_generics #where_clause {
+            fn binding_descriptions() -> ::std::vec::Vec<::favilla::vertex::__private::vk::VertexInputBindingDescription> {
+                ::std::vec![::favilla::vertex::__private::vk::VertexInputBindingDescription {
+                    binding: #binding,
+                    stride: ::core::mem::size_of::<Self>() as u32,
+                    input_rate: #input_rate,
+                }]
+            }
+
+            fn attribute_descriptions() -> ::std::vec::Vec<::favilla::vertex::__private::vk::VertexInputAttributeDescription> {
+                ::std::vec![#(#attributes),*]
+            }
+        }
+    })
+}
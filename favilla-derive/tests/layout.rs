@@ -0,0 +1,37 @@
+use cgmath::{Matrix4, Vector2, Vector3, Vector4};
+use favilla::layout::{Padding, Std140, Std430};
+
+#[derive(Copy, Clone, Std140, Std430)]
+#[repr(C)]
+struct Light {
+    position: Vector3<f32>,
+    intensity: f32,
+    colour: Vector4<f32>,
+}
+
+#[derive(Copy, Clone, Std140)]
+#[repr(C)]
+struct Scene {
+    view_projection: Matrix4<f32>,
+    lights: [Light; 2],
+    ambient: Vector3<f32>,
+    _padding: Padding<4>,
+}
+
+#[derive(Copy, Clone, Std430)]
+#[repr(C)]
+struct Particle {
+    position: Vector2<f32>,
+    velocity: Vector2<f32>,
+    weights: [f32; 4],
+}
+
+#[test]
+fn derived_layouts() {
+    assert_eq!(<Light as Std140>::ALIGNMENT, 16);
+    assert_eq!(<Light as Std140>::SIZE, 32);
+    assert_eq!(<Light as Std430>::SIZE, 32);
+    assert_eq!(<Scene as Std140>::SIZE, 144);
+    assert_eq!(<Particle as Std430>::ALIGNMENT, 8);
+    assert_eq!(<Particle as Std430>::SIZE, 32);
+}
@@ -0,0 +1,67 @@
+use ash::vk;
+use cgmath::{Vector2, Vector4};
+use favilla::vertex::{Unorm8x4, Vertex, VertexInput};
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+#[derive(Vertex)]
+struct SpriteVertex {
+    position: Vector2<f32>,
+    colour: Vector4<f32>,
+    tex_coords: [f32; 2],
+}
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+#[derive(Vertex)]
+#[vertex(binding = 1, location = 3, instance)]
+struct Instance {
+    offset: [f32; 3],
+    tint: Unorm8x4,
+    #[vertex(format = "R16G16_SNORM")]
+    packed: [i16; 2],
+}
+
+#[test]
+fn vertex_layout() {
+    let bindings = SpriteVertex::binding_descriptions();
+    assert_eq!(bindings.len(), 1);
+    assert_eq!(bindings[0].binding, 0);
+    assert_eq!(bindings[0].stride, 32);
+    assert_eq!(bindings[0].input_rate, vk::VertexInputRate::VERTEX);
+
+    let attributes = SpriteVertex::attribute_descriptions();
+    let summary = attributes
+        .iter()
+        .map(|a| (a.location, a.binding, a.format, a.offset))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (0, 0, vk::Format::R32G32_SFLOAT, 0),
+            (1, 0, vk::Format::R32G32B32A32_SFLOAT, 8),
+            (2, 0, vk::Format::R32G32_SFLOAT, 24),
+        ]
+    );
+}
+
+#[test]
+fn instance_layout() {
+    let bindings = Instance::binding_descriptions();
+    assert_eq!(bindings[0].binding, 1);
+    assert_eq!(bindings[0].input_rate, vk::VertexInputRate::INSTANCE);
+    assert_eq!(bindings[0].stride, 20);
+
+    let summary = Instance::attribute_descriptions()
+        .iter()
+        .map(|a| (a.location, a.binding, a.format, a.offset))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (3, 1, vk::Format::R32G32B32_SFLOAT, 0),
+            (4, 1, vk::Format::R8G8B8A8_UNORM, 12),
+            (5, 1, vk::Format::R16G16_SNORM, 16),
+        ]
+    );
+}
@@ -1,20 +1,24 @@
 use ash::extensions::ext::DebugUtils;
 use ash::extensions::khr::Swapchain;
-use ash::vk::{
-    DeviceSize, Handle, ImageViewCreateInfo, IndexType, MemoryPropertyFlags, SharingMode,
-};
+use ash::vk::{Handle, MemoryPropertyFlags};
 use ash::{vk, Entry};
 use cgmath::{vec2, vec4, Matrix4};
 use cstr::cstr;
 use favilla::app::{App, AppSettings};
-use favilla::buffer::{StagingBufferWithDedicatedAllocation, VulkanBufferWithDedicatedAllocation};
+use favilla::buffer::StagingBufferWithDedicatedAllocation;
 use favilla::camera::Camera;
 use favilla::cleanup_queue::CleanupQueue;
 use favilla::debug_utils::DebugUtilsHelper;
+use favilla::dynamic_buffer::DynamicBuffer;
+use favilla::format::FormatRequirements;
 use favilla::frame_data::FrameDataManager;
 use favilla::memory::find_memory_type_index;
 use favilla::push_buffer::PushBuffer;
+use favilla::sampler::{SamplerCache, SamplerDescription};
 use favilla::swapchain::SwapchainManager;
+use favilla::texture::load::ColorSpace;
+use favilla::texture::ImageAccess;
+use favilla::upload_batch::UploadBatch;
 use favilla::vk_engine::VulkanEngine;
 use favilla_examples::*;
 use std::default::Default;
@@ -23,7 +27,6 @@ use std::os::raw::c_char;
 use tracing::level_filters::LevelFilter;
 use tracing::{event, info, Level};
 use tracing_subscriber::fmt::Subscriber;
-use vk::{DependencyFlags, PipelineStageFlags};
 use vk_shader_macros::include_glsl;
 use winit::{
     event::{Event, WindowEvent},
@@ -176,24 +179,12 @@ fn main() -> anyhow::Result<()> {
 
         let mut cleanup_queue = CleanupQueue::new(vk_engine.num_frames as _);
 
-        let mut staging_buffer_per_frame = (0..vk_engine.num_frames)
-            .map(|_| {
-                StagingBufferWithDedicatedAllocation::allocate(
-                    &vk_engine,
-                    3,
-                    vk::BufferUsageFlags::TRANSFER_SRC,
-                    vk::SharingMode::EXCLUSIVE,
-                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-                )
-            })
-            .collect::<Vec<_>>();
-
-        let mut vertex_buffer = VulkanBufferWithDedicatedAllocation::allocate(
+        let mut vertex_buffer = DynamicBuffer::new(
             &vk_engine,
             3,
-            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
-            vk::SharingMode::EXCLUSIVE,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
         );
 
         let mut index_buffer =
@@ -286,10 +277,14 @@ fn main() -> anyhow::Result<()> {
 
         // TEXTURE FUN START
 
-        let texture_one_data = &[0xFF0000FFu32];
-        let texture_two_data = &[0xFFFFFFFFu32, 0xFFFFFFFFu32, 0xFFFFFFFFu32, 0xFFFFFFFFu32];
-
-        let image_format = vk::Format::R8G8B8A8_SRGB;
+        let image_format = FormatRequirements::SRGB_TEXTURE
+            .choose(&app.instance, vk_engine.physical_device)
+            .expect("No supported format for sampled sRGB textures");
+        // A single opaque red texel, in the channel order of the chosen format.
+        let texture_one_data: &[u32] = match image_format {
+            vk::Format::B8G8R8A8_SRGB => &[0xFFFF0000],
+            _ => &[0xFF0000FF],
+        };
         let num_texture_array_layers = 1;
 
         // Image one: 1x1
@@ -303,9 +298,9 @@ fn main() -> anyhow::Result<()> {
                 height: 1,
                 depth: 1,
             },
-            1,
+            num_texture_array_layers,
+            vk::ImageUsageFlags::SAMPLED,
         )?;
-        let texture_one_data_size = std::mem::size_of_val(texture_one_data) as DeviceSize;
         let image_one_mem_req = image_one.get_memory_requirements(&vk_engine.device);
 
         let total_texture_size: vk::DeviceSize = 1024 * 1024 * 16;
@@ -352,15 +347,6 @@ fn main() -> anyhow::Result<()> {
             texture_memory_allocator
         );
 
-        let mut image_one_staging_buffer = StagingBufferWithDedicatedAllocation::allocate(
-            &vk_engine,
-            texture_one_data_size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            SharingMode::EXCLUSIVE,
-            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-        );
-        image_one_staging_buffer.buffer.write(texture_one_data, 0);
-
         let image_one_memory = texture_memory_allocator
             .allocate(image_one_mem_req)
             .expect("Failed to allocate sub memory for image one");
@@ -375,103 +361,38 @@ fn main() -> anyhow::Result<()> {
             .bind_memory(&vk_engine, image_one_memory.memory, image_one_memory.offset)
             .expect("Failed to bind image memory");
 
-        image_one.copy_staging_to_image(
-            &vk_engine,
-            frame_manager.command_pool,
-            &image_one_staging_buffer.buffer,
-            0,
-        );
-
-        image_one_staging_buffer.destroy(&vk_engine.device);
-
-        let image_view_one = vk_engine
-            .device
-            .create_image_view(
-                &ImageViewCreateInfo::builder()
-                    .image(image_one.image)
-                    .format(image_format)
-                    .components(vk::ComponentMapping::default())
-                    .view_type(vk::ImageViewType::TYPE_2D)
-                    .subresource_range(
-                        vk::ImageSubresourceRange::builder()
-                            .aspect_mask(vk::ImageAspectFlags::COLOR)
-                            .base_mip_level(0)
-                            .level_count(1)
-                            .base_array_layer(0)
-                            .layer_count(num_texture_array_layers)
-                            .build(),
-                    ),
-                None,
+        let image_view_one = image_one
+            .create_view(
+                &vk_engine.device,
+                vk::ImageViewType::TYPE_2D,
+                image_one.full_range(),
             )
             .expect("Couldn't create image view.");
 
-        // Image two: 2x2
+        // Image two: loaded from a PNG file
 
-        let mut image_two = favilla::texture::Texture::new(
+        let mut image_two = favilla::texture::load::load_from_memory(
             &vk_engine,
-            image_format,
-            vk::ImageType::TYPE_2D,
-            vk::Extent3D {
-                width: 2,
-                height: 2,
-                depth: 1,
-            },
-            1,
+            frame_manager.command_pool,
+            &mut texture_memory_allocator,
+            include_bytes!("../../assets/checkerboard.png"),
+            ColorSpace::Srgb,
+            ImageAccess::FRAGMENT_SHADER_READ,
         )?;
-        let texture_two_data_size = std::mem::size_of_val(texture_two_data) as DeviceSize;
-        let image_two_mem_req = image_two.get_memory_requirements(&vk_engine.device);
-        let mut image_two_staging_buffer = StagingBufferWithDedicatedAllocation::allocate(
-            &vk_engine,
-            texture_two_data_size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            SharingMode::EXCLUSIVE,
-            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
-        );
-        image_two_staging_buffer.buffer.write(texture_two_data, 0);
-
-        let image_two_memory = texture_memory_allocator
-            .allocate(image_two_mem_req)
-            .expect("Failed to allocate sub memory for image one");
 
         event!(
             Level::DEBUG,
-            "sub allocation for image one: {:?}",
-            image_two_memory
-        );
-
-        image_two
-            .bind_memory(&vk_engine, image_two_memory.memory, image_two_memory.offset)
-            .expect("Failed to bind image memory");
-
-        image_two.copy_staging_to_image(
-            &vk_engine,
-            frame_manager.command_pool,
-            &image_two_staging_buffer.buffer,
-            0,
+            "sub allocation for image two: {:?}",
+            image_two.allocation
         );
 
-        image_two_staging_buffer.destroy(&vk_engine.device);
-
-        let image_view_two = vk_engine
-            .device
-            .create_image_view(
-                &ImageViewCreateInfo::builder()
-                    .image(image_two.image)
-                    .format(image_format)
-                    .components(vk::ComponentMapping::default())
-                    .view_type(vk::ImageViewType::TYPE_2D)
-                    .subresource_range(
-                        vk::ImageSubresourceRange::builder()
-                            .aspect_mask(vk::ImageAspectFlags::COLOR)
-                            .base_mip_level(0)
-                            .level_count(1)
-                            .base_array_layer(0)
-                            .layer_count(num_texture_array_layers)
-                            .build(),
-                    ),
-                None,
-            )
-            .expect("Couldn't create image view.");
+        let mut upload_batch = UploadBatch::new();
+        upload_batch.upload_texture(
+            texture_one_data,
+            &mut image_one,
+            ImageAccess::FRAGMENT_SHADER_READ,
+        )?;
+        upload_batch.submit(&vk_engine, frame_manager.command_pool);
 
         let texture_binding = vk::DescriptorSetLayoutBinding::builder()
             .binding(0)
@@ -491,17 +412,11 @@ fn main() -> anyhow::Result<()> {
             .create_descriptor_set_layout(&texture_descriptor_set_layout_create_info, None)
             .expect("Failed to allocate descriptor set layout");
 
-        let sampler = vk_engine
-            .device
-            .create_sampler(
-                &vk::SamplerCreateInfo::builder()
-                    .mag_filter(vk::Filter::NEAREST)
-                    .min_filter(vk::Filter::NEAREST)
-                    .address_mode_u(vk::SamplerAddressMode::REPEAT)
-                    .address_mode_v(vk::SamplerAddressMode::REPEAT)
-                    .address_mode_w(vk::SamplerAddressMode::REPEAT)
-                    .build(),
-                None,
+        let mut sampler_cache = SamplerCache::new(&app.instance, &vk_engine);
+        let sampler = sampler_cache
+            .get(
+                &vk_engine.device,
+                SamplerDescription::nearest(vk::SamplerAddressMode::REPEAT),
             )
             .expect("Failed to create image sampler");
 
@@ -534,7 +449,7 @@ fn main() -> anyhow::Result<()> {
                     vk::DescriptorImageInfo::builder()
                         .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
                         .sampler(sampler)
-                        .image_view(image_view_two)
+                        .image_view(image_two.view)
                         .build(),
                 ])
                 .build()],
@@ -637,16 +552,12 @@ fn main() -> anyhow::Result<()> {
                     vk_engine.device.destroy_image_view(image_view_one, None);
                     image_one.destroy(&vk_engine.device);
 
-                    vk_engine.device.destroy_image_view(image_view_two, None);
                     image_two.destroy(&vk_engine.device);
 
                     texture_memory_allocator.destroy(&vk_engine.device);
 
                     index_buffer.destroy(&vk_engine.device);
 
-                    for staging_buffer in &mut staging_buffer_per_frame {
-                        staging_buffer.destroy(&vk_engine.device);
-                    }
                     vertex_buffer.destroy(&vk_engine.device);
 
                     cleanup_queue.destroy(&vk_engine.device);
@@ -663,7 +574,7 @@ fn main() -> anyhow::Result<()> {
                         buffer.destroy(&vk_engine.device);
                     }
 
-                    vk_engine.device.destroy_sampler(sampler, None);
+                    sampler_cache.destroy(&vk_engine.device);
 
                     vk_engine.device.destroy_pipeline(graphics_pipeline, None);
                     vk_engine
@@ -808,60 +719,28 @@ fn main() -> anyhow::Result<()> {
                         // NLL makes this redundant, but it's clearer this way.
                     }
 
-                    let staging_buffer = &mut staging_buffer_per_frame[frame as usize];
-
-                    if staging_buffer.buffer.buffer.length < push_buffer.capacity() as _ {
-                        let new_staging_buffer = StagingBufferWithDedicatedAllocation::allocate(
-                            &vk_engine,
-                            push_buffer.capacity() as _,
-                            vk::BufferUsageFlags::TRANSFER_SRC,
-                            vk::SharingMode::EXCLUSIVE,
-                            vk::MemoryPropertyFlags::HOST_VISIBLE
-                                | vk::MemoryPropertyFlags::HOST_COHERENT,
-                        );
-
-                        event!(
-                            Level::DEBUG,
-                            "old staging buffer memory: {:?}, new staging buffer memory: {:?}",
-                            staging_buffer.memory,
-                            new_staging_buffer.memory
-                        );
-
-                        let old_staging_buffer =
-                            std::mem::replace(staging_buffer, new_staging_buffer);
-                        event!(Level::DEBUG, "replaced staging buffer");
-                        cleanup_queue.queue(old_staging_buffer);
-                    }
-
-                    if vertex_buffer.buffer.length < push_buffer.capacity() as _ {
-                        let new_vertex_buffer = VulkanBufferWithDedicatedAllocation::allocate(
-                            &vk_engine,
-                            push_buffer.capacity() as _,
-                            vk::BufferUsageFlags::TRANSFER_DST
-                                | vk::BufferUsageFlags::VERTEX_BUFFER,
-                            vk::SharingMode::EXCLUSIVE,
-                            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-                        );
+                    let vertex_buffer_reallocated = vertex_buffer.sync(
+                        &vk_engine,
+                        command_buffer,
+                        &push_buffer,
+                        &mut cleanup_queue,
+                    );
 
+                    if vertex_buffer_reallocated {
                         if let Some(ref debug_utils_helper) = debug_utils_helper {
                             debug_utils_helper
                                 .set_object_name(
                                     &vk_engine.device,
-                                    new_vertex_buffer.buffer.buffer.as_raw(),
+                                    vertex_buffer.buffer.buffer.buffer.as_raw(),
                                     vk::ObjectType::BUFFER,
                                     cstr!("Sprite Vertices"),
                                 )
                                 .expect("Could not set vertex buffer name");
                             event!(Level::DEBUG, "set vertex buffer name!");
                         }
-
-                        let old_vertex_buffer =
-                            std::mem::replace(&mut vertex_buffer, new_vertex_buffer);
-                        event!(Level::DEBUG, "replaced vertex buffer");
-                        cleanup_queue.queue(old_vertex_buffer);
                     }
 
-                    if index_buffer.buffer.length < push_buffer.capacity() as _ {
+                    if index_buffer.length() < push_buffer.capacity() as _ {
                         let new_index_buffer = create_index_buffer(
                             &vk_engine,
                             frame_manager.command_pool,
@@ -873,7 +752,7 @@ fn main() -> anyhow::Result<()> {
                             debug_utils_helper
                                 .set_object_name(
                                     &vk_engine.device,
-                                    new_index_buffer.buffer.buffer.as_raw(),
+                                    new_index_buffer.buffer.buffer.buffer.as_raw(),
                                     vk::ObjectType::BUFFER,
                                     cstr!("Sprite Indices"),
                                 )
@@ -888,54 +767,11 @@ fn main() -> anyhow::Result<()> {
                         cleanup_queue.queue(old_index_buffer);
                     }
 
-                    staging_buffer.buffer.write(push_buffer.data(), 0);
-
-                    // Execution barrier *before* copying from the staging buffer to the vertex buffer:
-                    // the vertex buffer might still be used by last frame's rendering process.
-
-                    vk_engine.device.cmd_pipeline_barrier(
-                        command_buffer,
-                        PipelineStageFlags::VERTEX_INPUT, // Finish previous frame's vertex shader
-                        PipelineStageFlags::TRANSFER,     // Wait before uploading new vertices
-                        DependencyFlags::empty(),
-                        &[],
-                        &[],
-                        &[],
-                    );
-
-                    staging_buffer
-                        .buffer
-                        .buffer
-                        .copy(
-                            &vk_engine,
-                            command_buffer,
-                            &mut vertex_buffer.buffer,
-                            0,
-                            0,
-                            push_buffer.len() as _,
-                        )
-                        .unwrap();
-
                     // Update camera buffer (not strictly necessary since the camera is completely static right now)
                     let camera_buffer = &mut camera_buffer_per_frame[frame as usize];
                     camera_buffer
                         .buffer
-                        .write(&[cam.view_projection_matrix()], 0);
-
-                    let memory_barrier_transfer_render = vk::MemoryBarrier::builder()
-                        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
-                        .dst_access_mask(vk::AccessFlags::VERTEX_ATTRIBUTE_READ)
-                        .build();
-
-                    vk_engine.device.cmd_pipeline_barrier(
-                        command_buffer,
-                        PipelineStageFlags::TRANSFER, // Finish vertex upload
-                        PipelineStageFlags::VERTEX_INPUT, // Wait before vertices are used
-                        DependencyFlags::empty(),
-                        &[memory_barrier_transfer_render],
-                        &[],
-                        &[],
-                    );
+                        .write_std140(&[cam.view_projection_matrix()], 0);
 
                     let clear_values = [vk::ClearValue {
                         color: vk::ClearColorValue {
@@ -997,16 +833,11 @@ fn main() -> anyhow::Result<()> {
                     vk_engine.device.cmd_bind_vertex_buffers(
                         command_buffer,
                         0,
-                        &[vertex_buffer.buffer.buffer],
+                        &[vertex_buffer.buffer.buffer.buffer],
                         &[0],
                     );
 
-                    vk_engine.device.cmd_bind_index_buffer(
-                        command_buffer,
-                        index_buffer.buffer.buffer,
-                        0,
-                        IndexType::UINT32,
-                    );
+                    index_buffer.bind(&vk_engine.device, command_buffer, 0);
 
                     vk_engine
                         .device
@@ -4,16 +4,17 @@
 use ash::extensions::khr::Surface;
 use ash::vk;
 use ash::vk::PhysicalDevice;
-use ash::vk::{PipelineLayout, ShaderModule, VertexInputRate};
+use ash::vk::{PipelineLayout, ShaderModule};
 use cgmath::{Vector2, Vector4, Zero};
-use favilla::buffer::{StagingBufferWithDedicatedAllocation, VulkanBufferWithDedicatedAllocation};
+use favilla::buffer::StagingBufferWithDedicatedAllocation;
+use favilla::index_buffer::{IndexBuffer, IndexElement};
+use favilla::vertex::{Vertex, VertexInput};
 use favilla::vk_engine::VulkanEngine;
-use memoffset::offset_of;
 use std::borrow::Cow;
 use std::ffi::{CStr, CString};
 use tracing::{event, Level};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Vertex)]
 pub struct Vertex {
     pub position: Vector2<f32>,
     pub colour: Vector4<f32>,
@@ -33,14 +34,15 @@ impl Default for Vertex {
 /// # Safety
 /// Requires sufficient memory to be available.
 /// Can only be called on the thread that is able to submit to the given command pool.
-pub unsafe fn create_index_buffer<F>(
+pub unsafe fn create_index_buffer<T, F>(
     vk_engine: &VulkanEngine,
     command_pool: vk::CommandPool,
     length: u32,
     f: F,
-) -> VulkanBufferWithDedicatedAllocation<u32>
+) -> IndexBuffer<T>
 where
-    F: Fn(u32) -> u32,
+    T: IndexElement,
+    F: Fn(u32) -> T,
 {
     let mut index_staging_buffer = StagingBufferWithDedicatedAllocation::allocate(
         vk_engine,
@@ -49,14 +51,14 @@ where
         vk::SharingMode::EXCLUSIVE,
         vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
     );
-    let mut index_buffer = VulkanBufferWithDedicatedAllocation::allocate(
+    let mut index_buffer = IndexBuffer::allocate(
         vk_engine,
         length as _,
-        vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
+        vk::BufferUsageFlags::TRANSFER_DST,
         vk::SharingMode::EXCLUSIVE,
         vk::MemoryPropertyFlags::DEVICE_LOCAL,
     );
-    let index_data: Vec<u32> = (0..length).map(f).collect::<_>();
+    let index_data: Vec<T> = (0..length).map(f).collect::<_>();
     index_staging_buffer.buffer.write(&index_data, 0);
 
     vk_engine.one_time_submit(command_pool, |cmd_buffer| {
@@ -66,7 +68,7 @@ where
             .copy(
                 vk_engine,
                 cmd_buffer,
-                &mut index_buffer.buffer,
+                &mut index_buffer.buffer.buffer,
                 0,
                 0,
                 length as _,
@@ -147,32 +149,8 @@ pub unsafe fn create_graphics_pipeline(
         },
     ];
 
-    let vertex_input_attribute_desc = [
-        vk::VertexInputAttributeDescription {
-            location: 0,
-            binding: 0,
-            format: vk::Format::R32G32_SFLOAT,
-            offset: offset_of!(Vertex, position) as _,
-        },
-        vk::VertexInputAttributeDescription {
-            location: 1,
-            binding: 0,
-            format: vk::Format::R32G32B32A32_SFLOAT,
-            offset: offset_of!(Vertex, colour) as _,
-        },
-        vk::VertexInputAttributeDescription {
-            location: 2,
-            binding: 0,
-            format: vk::Format::R32G32_SFLOAT,
-            offset: offset_of!(Vertex, tex_coords) as _,
-        },
-    ];
-
-    let vertex_binding_descriptions = [vk::VertexInputBindingDescription::builder()
-        .binding(0)
-        .stride(std::mem::size_of::<Vertex>() as u32)
-        .input_rate(VertexInputRate::VERTEX)
-        .build()];
+    let vertex_input_attribute_desc = Vertex::attribute_descriptions();
+    let vertex_binding_descriptions = Vertex::binding_descriptions();
 
     let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
         .vertex_attribute_descriptions(&vertex_input_attribute_desc)
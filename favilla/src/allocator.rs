@@ -0,0 +1,79 @@
+use ash::vk::{DeviceMemory, MemoryRequirements};
+use ash::{vk, Device};
+use std::ffi::c_void;
+use std::ptr::NonNull;
+use thiserror::Error;
+
+/// An allocation within a larger chunk of allocated memory.
+#[derive(Debug)]
+pub struct SubAllocation {
+    pub memory: DeviceMemory,
+    pub offset: u64,
+    pub size: u64,
+    /// Host pointer to the start of the allocation if the underlying memory is mapped.
+    pub mapped_ptr: Option<NonNull<c_void>>,
+}
+
+// The mapped pointer is only an address into persistently mapped memory;
+// access to it is unsafe and has to be synchronized by the user anyway.
+unsafe impl Send for SubAllocation {}
+
+#[derive(Error, Debug)]
+pub enum SubAllocationError {
+    #[error("Out of memory")]
+    OutOfMemory,
+    #[error("Memory type {0} is not allowed by the memory requirements")]
+    IncompatibleMemoryType(u32),
+    #[error("The allocator's memory is not mapped")]
+    NotMapped,
+    #[error("The allocator's memory was not allocated with the DEVICE_ADDRESS flag")]
+    MissingDeviceAddressFlag,
+}
+
+/// Common interface of allocators handing out parts of larger device memory allocations.
+pub trait Allocator {
+    /// The memory type index of all memory handed out by this allocator.
+    fn memory_type_index(&self) -> u32;
+
+    /// The flags the allocator's memory was allocated with.
+    fn allocate_flags(&self) -> vk::MemoryAllocateFlags {
+        vk::MemoryAllocateFlags::empty()
+    }
+
+    /// True iff the allocator's memory is mapped, i.e. its allocations carry a host pointer.
+    fn is_mapped(&self) -> bool;
+
+    /// True iff `allocation` was made by this allocator.
+    fn owns(&self, allocation: &SubAllocation) -> bool;
+
+    /// Try to get a free chunk of memory satisfying the given requirements.
+    /// Allocators that allocate memory on demand, such as `FreeListAllocator`, allocate it from `device`.
+    /// # Safety
+    /// Memory must not have been freed. The allocator's memory must belong to `device`.
+    unsafe fn allocate(
+        &mut self,
+        device: &Device,
+        memory_req: MemoryRequirements,
+    ) -> Result<SubAllocation, SubAllocationError>;
+
+    /// Return the memory of `allocation` to the allocator.
+    /// Allocators that cannot free individual allocations, such as `LinearAllocator`, ignore this.
+    /// # Safety
+    /// The allocation must have been made by this allocator and must not be used anymore.
+    unsafe fn free(&mut self, allocation: SubAllocation);
+}
+
+pub fn get_aligned_offset(offset: u64, alignment: u64) -> u64 {
+    let misalignment = offset % alignment;
+    let padding = if misalignment == 0 {
+        0
+    } else {
+        alignment - misalignment
+    };
+    offset + padding
+}
+
+/// Check whether memory of the given memory type index can be used for the given requirements.
+pub fn supports_memory_type(memory_req: &vk::MemoryRequirements, memory_type_index: u32) -> bool {
+    (1 << memory_type_index) & memory_req.memory_type_bits != 0
+}
@@ -1,3 +1,5 @@
+use crate::allocator::{Allocator, SubAllocation, SubAllocationError};
+use crate::layout::{AssertStd140Array, AssertStd430Array, Std140, Std430};
 use crate::memory::find_memory_type_index;
 use crate::vk_engine::VulkanEngine;
 
@@ -109,6 +111,28 @@ impl<T> VulkanBuffer<T> {
             .expect("Binding memory buffer failed");
     }
 
+    /// Get the device address of the buffer.
+    /// Uses the Vulkan 1.2 core entry point; on Vulkan 1.1 with `VK_KHR_buffer_device_address`, use
+    /// `ash::extensions::khr::BufferDeviceAddress` instead.
+    /// # Safety
+    /// The buffer must have been created with `SHADER_DEVICE_ADDRESS` usage and be bound to memory
+    /// allocated with the `DEVICE_ADDRESS` flag. The device must use Vulkan 1.2 and have been created with
+    /// the `bufferDeviceAddress` feature enabled (in `PhysicalDeviceVulkan12Features`).
+    pub unsafe fn device_address(&self, device: &Device) -> vk::DeviceAddress {
+        device.get_buffer_device_address(
+            &vk::BufferDeviceAddressInfo::builder()
+                .buffer(self.buffer)
+                .build(),
+        )
+    }
+
+    /// Get a typed pointer to the first element of the buffer, e.g. for use in push constants.
+    /// # Safety
+    /// See `device_address`.
+    pub unsafe fn device_ptr(&self, device: &Device) -> DevicePtr<T> {
+        DevicePtr::new(self.device_address(device))
+    }
+
     /// Frees the buffer resource held by `self`.
     /// # Safety
     /// Buffer must not be in use anymore.
@@ -117,6 +141,74 @@ impl<T> VulkanBuffer<T> {
     }
 }
 
+/// A typed device address pointing to elements of type `T`.
+/// Has the same layout as `vk::DeviceAddress`, so it can be written to push constants
+/// and buffers that are read as `buffer_reference`s in shaders.
+/// Device addresses require Vulkan 1.2 or `VK_KHR_buffer_device_address`, and the `bufferDeviceAddress`
+/// feature has to be enabled when creating the device; see `VulkanBuffer::device_address`.
+#[repr(transparent)]
+pub struct DevicePtr<T> {
+    address: vk::DeviceAddress,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> DevicePtr<T> {
+    /// Create a new pointer from a raw device address.
+    pub fn new(address: vk::DeviceAddress) -> Self {
+        Self {
+            address,
+            phantom: PhantomData {},
+        }
+    }
+
+    /// A null pointer.
+    pub fn null() -> Self {
+        Self::new(0)
+    }
+
+    /// True iff the pointer is null.
+    pub fn is_null(&self) -> bool {
+        self.address == 0
+    }
+
+    /// Get the raw device address.
+    pub fn address(&self) -> vk::DeviceAddress {
+        self.address
+    }
+
+    /// Get a pointer to the element `count` elements after the one pointed to by `self`.
+    pub fn offset(self, count: u64) -> Self {
+        Self::new(self.address + count * std::mem::size_of::<T>() as u64)
+    }
+
+    /// Reinterpret the pointer as pointing to elements of type `U`.
+    pub fn cast<U>(self) -> DevicePtr<U> {
+        DevicePtr::new(self.address)
+    }
+}
+
+impl<T> Clone for DevicePtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for DevicePtr<T> {}
+
+impl<T> PartialEq for DevicePtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.address == other.address
+    }
+}
+
+impl<T> Eq for DevicePtr<T> {}
+
+impl<T> std::fmt::Debug for DevicePtr<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DevicePtr({:#x})", self.address)
+    }
+}
+
 /// A Vulkan buffer with a dedicated memory allocation.
 pub struct VulkanBufferWithDedicatedAllocation<T> {
     pub buffer: VulkanBuffer<T>,
@@ -125,8 +217,10 @@ pub struct VulkanBufferWithDedicatedAllocation<T> {
 
 impl<T> VulkanBufferWithDedicatedAllocation<T> {
     /// Allocates a new buffer.
+    /// If `usage` contains `SHADER_DEVICE_ADDRESS`, the memory is allocated with the `DEVICE_ADDRESS` flag.
     /// # Safety
-    /// Requires a valid device with sufficient memory.
+    /// Requires a valid device with sufficient memory. `SHADER_DEVICE_ADDRESS` usage requires Vulkan 1.2
+    /// or `VK_KHR_buffer_device_address`, with the `bufferDeviceAddress` feature enabled.
     pub unsafe fn allocate(
         vk_engine: &VulkanEngine,
         length: u64,
@@ -154,7 +248,14 @@ impl<T> VulkanBufferWithDedicatedAllocation<T> {
             memory_type_index
         );
 
-        let memory = vk_engine.allocate_memory(mem_req, memory_type_index);
+        let allocate_flags = if usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            vk::MemoryAllocateFlags::DEVICE_ADDRESS
+        } else {
+            vk::MemoryAllocateFlags::empty()
+        };
+        let memory = vk_engine
+            .try_allocate_memory_with_flags(mem_req, memory_type_index, allocate_flags)
+            .expect("Failed to allocate memory");
         buffer.bind_memory(vk_engine, memory, 0);
 
         Self { memory, buffer }
@@ -169,6 +270,65 @@ impl<T> VulkanBufferWithDedicatedAllocation<T> {
     }
 }
 
+/// A Vulkan buffer bound to memory sub-allocated from an `Allocator`.
+/// The memory is owned by the allocator.
+pub struct VulkanBufferWithSubAllocation<T> {
+    pub buffer: VulkanBuffer<T>,
+    pub allocation: SubAllocation,
+}
+
+impl<T> VulkanBufferWithSubAllocation<T> {
+    /// Creates a new buffer and binds memory from the given allocator to it.
+    /// # Safety
+    /// Requires a valid device; the allocator's memory must belong to the same device.
+    pub unsafe fn allocate(
+        vk_engine: &VulkanEngine,
+        length: u64,
+        usage: vk::BufferUsageFlags,
+        sharing_mode: vk::SharingMode,
+        allocator: &mut dyn Allocator,
+    ) -> Result<Self, SubAllocationError> {
+        if usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS)
+            && !allocator
+                .allocate_flags()
+                .contains(vk::MemoryAllocateFlags::DEVICE_ADDRESS)
+        {
+            return Err(SubAllocationError::MissingDeviceAddressFlag);
+        }
+
+        let memory_type_index = allocator.memory_type_index();
+        let memory_property_flags = vk_engine.device_memory_properties.memory_types
+            [memory_type_index as usize]
+            .property_flags;
+        let mut buffer = VulkanBuffer::new(
+            vk_engine,
+            length,
+            usage,
+            sharing_mode,
+            memory_property_flags,
+        );
+        let mem_req = buffer.get_memory_requirements(&vk_engine.device);
+
+        match allocator.allocate(&vk_engine.device, mem_req) {
+            Ok(allocation) => {
+                buffer.bind_memory(vk_engine, allocation.memory, allocation.offset);
+                Ok(Self { buffer, allocation })
+            }
+            Err(e) => {
+                buffer.destroy(&vk_engine.device);
+                Err(e)
+            }
+        }
+    }
+
+    /// Frees the buffer resource held by `self`. The memory is not freed.
+    /// # Safety
+    /// The buffer must not be used anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        self.buffer.destroy(device);
+    }
+}
+
 /// A wrapper for a staging buffer. Holds a `VulkanBuffer<T>` and a pointer to the mapped memory.
 pub struct StagingBuffer<T: Copy> {
     pub buffer: VulkanBuffer<T>,
@@ -185,6 +345,32 @@ impl<T: Copy> StagingBuffer<T> {
         ptr.copy_from_nonoverlapping(data.as_ptr(), len);
     }
 
+    /// Write the data to the staging buffer as a GLSL `std140` array, e.g. for uniform buffers.
+    /// Fails to compile if `T`'s layout doesn't match `std140`.
+    /// # Safety
+    /// The buffer must not be accessed without proper synchronisation.
+    pub unsafe fn write_std140(&mut self, data: &[T], offset_in_element_count: usize)
+    where
+        T: Std140,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = AssertStd140Array::<T>::OK;
+        self.write(data, offset_in_element_count);
+    }
+
+    /// Write the data to the staging buffer as a GLSL `std430` array, e.g. for storage buffers.
+    /// Fails to compile if `T`'s layout doesn't match `std430`.
+    /// # Safety
+    /// The buffer must not be accessed without proper synchronisation.
+    pub unsafe fn write_std430(&mut self, data: &[T], offset_in_element_count: usize)
+    where
+        T: Std430,
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = AssertStd430Array::<T>::OK;
+        self.write(data, offset_in_element_count);
+    }
+
     /// Frees the buffer resource held by `self`.
     /// # Safety
     /// The buffer must not be used anymore.
@@ -259,3 +445,72 @@ impl<T: Copy> StagingBufferWithDedicatedAllocation<T> {
         }
     }
 }
+
+/// A staging buffer bound to memory sub-allocated from an `Allocator`.
+/// The memory is owned by the allocator.
+pub struct StagingBufferWithSubAllocation<T: Copy> {
+    pub buffer: StagingBuffer<T>,
+    pub allocation: SubAllocation,
+}
+
+impl<T: Copy> StagingBufferWithSubAllocation<T> {
+    /// Creates a new staging buffer from memory of the given allocator.
+    /// The allocator's memory has to be mapped; the staging buffer points into the mapped memory
+    /// at the offset of its sub-allocation.
+    /// # Safety
+    /// Requires a valid device; the allocator's memory must belong to the same device.
+    pub unsafe fn allocate(
+        vk_engine: &VulkanEngine,
+        length: u64,
+        usage: vk::BufferUsageFlags,
+        sharing_mode: vk::SharingMode,
+        allocator: &mut dyn Allocator,
+    ) -> Result<Self, SubAllocationError> {
+        if !allocator.is_mapped() {
+            return Err(SubAllocationError::NotMapped);
+        }
+        let mut buffer = VulkanBufferWithSubAllocation::allocate(
+            vk_engine,
+            length,
+            usage,
+            sharing_mode,
+            allocator,
+        )?;
+
+        match buffer.allocation.mapped_ptr {
+            Some(ptr) => Ok(Self {
+                buffer: StagingBuffer {
+                    buffer: buffer.buffer,
+                    buffer_ptr: ptr.as_ptr() as *mut T,
+                },
+                allocation: buffer.allocation,
+            }),
+            None => {
+                buffer.destroy(&vk_engine.device);
+                allocator.free(buffer.allocation);
+                Err(SubAllocationError::NotMapped)
+            }
+        }
+    }
+
+    /// Frees the buffer resource held by `self`. The memory is not freed.
+    /// # Safety
+    /// The buffer must not be used anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        self.buffer.destroy(device);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_ptr_arithmetic() {
+        let ptr = DevicePtr::<[f32; 4]>::new(0x1000);
+        assert_eq!(ptr.offset(2).address(), 0x1020);
+        assert_eq!(ptr.cast::<u32>().offset(1).address(), 0x1004);
+        assert!(DevicePtr::<u32>::null().is_null());
+        assert_eq!(std::mem::size_of::<DevicePtr<u64>>(), 8);
+    }
+}
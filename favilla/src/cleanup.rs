@@ -11,12 +11,31 @@ impl Cleanup for vk::Buffer {
     }
 }
 
+impl Cleanup for vk::Image {
+    fn queue(self, queue: &mut CleanupQueue) {
+        queue.queue_image(self);
+    }
+}
+
+impl Cleanup for vk::ImageView {
+    fn queue(self, queue: &mut CleanupQueue) {
+        queue.queue_image_view(self);
+    }
+}
+
 impl Cleanup for vk::DeviceMemory {
     fn queue(self, queue: &mut CleanupQueue) {
         queue.queue_memory(self);
     }
 }
 
+/// The allocation is returned to its allocator by `CleanupQueue::free_released_allocations`.
+impl Cleanup for crate::allocator::SubAllocation {
+    fn queue(self, queue: &mut CleanupQueue) {
+        queue.queue_allocation(self);
+    }
+}
+
 impl<T> Cleanup for crate::buffer::VulkanBuffer<T> {
     fn queue(self, queue: &mut CleanupQueue) {
         self.buffer.queue(queue);
@@ -42,3 +61,53 @@ impl<T: Copy> Cleanup for crate::buffer::StagingBufferWithDedicatedAllocation<T>
         self.memory.queue(queue);
     }
 }
+
+impl<T> Cleanup for crate::buffer::VulkanBufferWithSubAllocation<T> {
+    fn queue(self, queue: &mut CleanupQueue) {
+        self.buffer.queue(queue);
+        self.allocation.queue(queue);
+    }
+}
+
+impl<T: Copy> Cleanup for crate::buffer::StagingBufferWithSubAllocation<T> {
+    fn queue(self, queue: &mut CleanupQueue) {
+        self.buffer.queue(queue);
+        self.allocation.queue(queue);
+    }
+}
+
+impl<T: crate::index_buffer::IndexElement> Cleanup for crate::index_buffer::IndexBuffer<T> {
+    fn queue(self, queue: &mut CleanupQueue) {
+        self.buffer.queue(queue);
+    }
+}
+
+impl<T: Copy> Cleanup for crate::dynamic_buffer::DynamicBuffer<T> {
+    fn queue(self, queue: &mut CleanupQueue) {
+        for staging_buffer in self.staging_buffers {
+            staging_buffer.queue(queue);
+        }
+        self.buffer.queue(queue);
+    }
+}
+
+impl Cleanup for crate::texture::Texture {
+    fn queue(self, queue: &mut CleanupQueue) {
+        self.image.queue(queue);
+    }
+}
+
+impl Cleanup for crate::texture::TextureWithDedicatedAllocation {
+    fn queue(self, queue: &mut CleanupQueue) {
+        self.texture.queue(queue);
+        self.memory.queue(queue);
+    }
+}
+
+impl Cleanup for crate::texture::LoadedTexture {
+    fn queue(self, queue: &mut CleanupQueue) {
+        self.view.queue(queue);
+        self.texture.queue(queue);
+        self.allocation.queue(queue);
+    }
+}
@@ -1,20 +1,33 @@
+use crate::allocator::{Allocator, SubAllocation};
 use crate::cleanup::Cleanup;
 use ash::vk;
 
 ///A queue for cleaning up resources. Deletion of resources will be delayed by N
 /// frames to avoid concurrency problems.
 /// N should be initialized with the number of frames that can be in flight at the same time.
-/// Supported resources are: ash::vk::Buffer and ash::vk::DeviceMemory.
+/// Supported resources are: ash::vk::Buffer, ash::vk::Image, ash::vk::ImageView and ash::vk::DeviceMemory.
+/// Sub-allocations are released as well, but have to be returned to their allocator with
+/// `free_released_allocations`.
 #[derive(Debug)]
 pub struct CleanupQueue {
     frame_queue: Vec<QueuedFrame>,
     current_frame_index: usize,
+    tick_count: u64,
+    released_allocations: Vec<SubAllocation>,
 }
 
+/// A point in the timeline of a `CleanupQueue`, counted in ticks.
+/// Used to defer releasing resources the queue doesn't manage itself, e.g. `LinearAllocator::rewind_deferred`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CleanupTick(u64);
+
 #[derive(Debug)]
 struct QueuedFrame {
     buffers: Vec<vk::Buffer>,
+    images: Vec<vk::Image>,
+    image_views: Vec<vk::ImageView>,
     memory: Vec<vk::DeviceMemory>,
+    allocations: Vec<SubAllocation>,
 }
 
 impl CleanupQueue {
@@ -22,6 +35,8 @@ impl CleanupQueue {
         Self {
             frame_queue: (0..num_frames).map(|_| QueuedFrame::new()).collect(),
             current_frame_index: 0,
+            tick_count: 0,
+            released_allocations: Vec::new(),
         }
     }
 
@@ -38,15 +53,41 @@ impl CleanupQueue {
         self.frame_queue[current_frame_index].push_buffer(buffer)
     }
 
+    pub fn queue_image(&mut self, image: vk::Image) {
+        let current_frame_index = self.get_current_frame_index();
+        self.frame_queue[current_frame_index].push_image(image)
+    }
+
+    pub fn queue_image_view(&mut self, image_view: vk::ImageView) {
+        let current_frame_index = self.get_current_frame_index();
+        self.frame_queue[current_frame_index].push_image_view(image_view)
+    }
+
     pub fn queue_memory(&mut self, memory: vk::DeviceMemory) {
         let current_frame_index = self.get_current_frame_index();
         self.frame_queue[current_frame_index].push_memory(memory)
     }
 
+    pub fn queue_allocation(&mut self, allocation: SubAllocation) {
+        let current_frame_index = self.get_current_frame_index();
+        self.frame_queue[current_frame_index].push_allocation(allocation)
+    }
+
     pub fn queue(&mut self, resource: impl Cleanup) {
         resource.queue(self);
     }
 
+    /// Get the tick at which resources used by the frames currently in flight can be released,
+    /// i.e. the tick at which resources queued now are deleted. See `has_passed`.
+    pub fn release_tick(&self) -> CleanupTick {
+        CleanupTick(self.tick_count + self.num_frames() as u64)
+    }
+
+    /// True iff the queue has been ticked up to `tick`.
+    pub fn has_passed(&self, tick: CleanupTick) -> bool {
+        self.tick_count >= tick.0
+    }
+
     /// Ticks the Cleanup Queue and deletes all resources that have been ticked `num_frames` times,
     /// # Safety
     /// Resources must be OK to free.
@@ -54,8 +95,15 @@ impl CleanupQueue {
         let index = self.current_frame_index;
 
         self.frame_queue[index].destroy(device);
+        self.released_allocations
+            .append(&mut self.frame_queue[index].allocations);
+
+        self.advance();
+    }
 
-        self.current_frame_index = (self.current_frame_index + 1) % self.num_frames()
+    pub(crate) fn advance(&mut self) {
+        self.current_frame_index = (self.current_frame_index + 1) % self.num_frames();
+        self.tick_count += 1;
     }
 
     /// Cleans up all resources immediately.
@@ -64,6 +112,22 @@ impl CleanupQueue {
     pub unsafe fn destroy(&mut self, device: &ash::Device) {
         for frame in &mut self.frame_queue {
             frame.destroy(device);
+            self.released_allocations.append(&mut frame.allocations);
+        }
+    }
+
+    /// Returns the sub-allocations released by `tick` or `destroy` that were made by `allocator` to it,
+    /// e.g. once per frame after ticking. Allocations of other allocators are kept until their allocator is passed.
+    /// # Safety
+    /// The allocator must be the one the allocations were made by if it `owns` them.
+    pub unsafe fn free_released_allocations(&mut self, allocator: &mut dyn Allocator) {
+        let (owned, others): (Vec<_>, Vec<_>) = self
+            .released_allocations
+            .drain(..)
+            .partition(|allocation| allocator.owns(allocation));
+        self.released_allocations = others;
+        for allocation in owned {
+            allocator.free(allocation);
         }
     }
 }
@@ -72,7 +136,10 @@ impl QueuedFrame {
     fn new() -> Self {
         Self {
             buffers: Vec::new(),
+            images: Vec::new(),
+            image_views: Vec::new(),
             memory: Vec::new(),
+            allocations: Vec::new(),
         }
     }
 
@@ -80,16 +147,39 @@ impl QueuedFrame {
         self.buffers.push(buffer);
     }
 
+    fn push_image(&mut self, image: vk::Image) {
+        self.images.push(image);
+    }
+
+    fn push_image_view(&mut self, image_view: vk::ImageView) {
+        self.image_views.push(image_view);
+    }
+
     fn push_memory(&mut self, memory: vk::DeviceMemory) {
         self.memory.push(memory);
     }
 
+    fn push_allocation(&mut self, allocation: SubAllocation) {
+        self.allocations.push(allocation);
+    }
+
     unsafe fn destroy(&mut self, device: &ash::Device) {
         for buffer in &self.buffers {
             device.destroy_buffer(*buffer, None);
         }
         self.buffers.clear();
 
+        // Views have to be destroyed before their images.
+        for image_view in &self.image_views {
+            device.destroy_image_view(*image_view, None);
+        }
+        self.image_views.clear();
+
+        for image in &self.images {
+            device.destroy_image(*image, None);
+        }
+        self.images.clear();
+
         for memory in &self.memory {
             device.free_memory(*memory, None);
         }
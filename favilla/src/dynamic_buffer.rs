@@ -0,0 +1,204 @@
+use crate::buffer::{StagingBufferWithDedicatedAllocation, VulkanBufferWithDedicatedAllocation};
+use crate::cleanup_queue::CleanupQueue;
+use crate::push_buffer::PushBuffer;
+use crate::vk_engine::VulkanEngine;
+use ash::{vk, Device};
+use tracing::{event, Level};
+
+/// A device-local buffer that is kept in sync with a `PushBuffer<T>`.
+/// Holds one host-visible staging buffer per in-flight frame and a single device-local buffer.
+/// Both grow geometrically when the data no longer fits; replaced buffers are retired through
+/// a `CleanupQueue`.
+pub struct DynamicBuffer<T: Copy> {
+    pub staging_buffers: Vec<StagingBufferWithDedicatedAllocation<T>>,
+    pub buffer: VulkanBufferWithDedicatedAllocation<T>,
+    pub usage: vk::BufferUsageFlags,
+    pub dst_stage_mask: vk::PipelineStageFlags,
+    pub dst_access_mask: vk::AccessFlags,
+}
+
+/// Get the new length of a buffer of length `current` that has to hold at least `required` elements.
+/// The result is never 0, since Vulkan buffers must not be empty.
+pub fn grown_length(current: u64, required: u64) -> u64 {
+    if current >= required.max(1) {
+        current
+    } else {
+        required.max(current * 2).max(1)
+    }
+}
+
+impl<T: Copy> DynamicBuffer<T> {
+    /// Creates a new dynamic buffer with the given initial length, which is clamped to at least 1.
+    /// `usage` is extended by `TRANSFER_DST`; `dst_stage_mask` and `dst_access_mask` describe how
+    /// the device-local buffer is used after being written, e.g. `VERTEX_INPUT` and `VERTEX_ATTRIBUTE_READ`.
+    /// # Safety
+    /// Requires a valid device with sufficient memory.
+    pub unsafe fn new(
+        vk_engine: &VulkanEngine,
+        length: u64,
+        usage: vk::BufferUsageFlags,
+        dst_stage_mask: vk::PipelineStageFlags,
+        dst_access_mask: vk::AccessFlags,
+    ) -> Self {
+        let length = length.max(1);
+        let staging_buffers = (0..vk_engine.num_frames)
+            .map(|_| Self::allocate_staging_buffer(vk_engine, length))
+            .collect();
+        let buffer = Self::allocate_device_buffer(vk_engine, length, usage);
+
+        Self {
+            staging_buffers,
+            buffer,
+            usage,
+            dst_stage_mask,
+            dst_access_mask,
+        }
+    }
+
+    unsafe fn allocate_staging_buffer(
+        vk_engine: &VulkanEngine,
+        length: u64,
+    ) -> StagingBufferWithDedicatedAllocation<T> {
+        StagingBufferWithDedicatedAllocation::allocate(
+            vk_engine,
+            length,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::SharingMode::EXCLUSIVE,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        )
+    }
+
+    unsafe fn allocate_device_buffer(
+        vk_engine: &VulkanEngine,
+        length: u64,
+        usage: vk::BufferUsageFlags,
+    ) -> VulkanBufferWithDedicatedAllocation<T> {
+        VulkanBufferWithDedicatedAllocation::allocate(
+            vk_engine,
+            length,
+            usage | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::SharingMode::EXCLUSIVE,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )
+    }
+
+    /// Get the length (in elements) of the device-local buffer.
+    pub fn length(&self) -> u64 {
+        self.buffer.buffer.length
+    }
+
+    /// Writes the content of `push_buffer` to the current frame's staging buffer and records the
+    /// copy to the device-local buffer into `command_buffer`, surrounded by the necessary barriers.
+    /// Buffers that are too small are replaced; the old ones are queued in `cleanup_queue`.
+    /// Returns true iff the device-local buffer was replaced.
+    /// # Safety
+    /// `command_buffer` must be the command buffer of the current frame and be in the recording state.
+    /// Must not be called inside a render pass.
+    pub unsafe fn sync(
+        &mut self,
+        vk_engine: &VulkanEngine,
+        command_buffer: vk::CommandBuffer,
+        push_buffer: &PushBuffer<T>,
+        cleanup_queue: &mut CleanupQueue,
+    ) -> bool {
+        let required = push_buffer.len() as u64;
+        let reallocated = self.length() < required;
+
+        if reallocated {
+            let length = grown_length(self.length(), required);
+            let new_buffer = Self::allocate_device_buffer(vk_engine, length, self.usage);
+            event!(
+                Level::DEBUG,
+                "growing dynamic buffer from {} to {} elements",
+                self.length(),
+                length
+            );
+            let old_buffer = std::mem::replace(&mut self.buffer, new_buffer);
+            cleanup_queue.queue(old_buffer);
+        }
+
+        let length = self.length();
+        let staging_buffer = &mut self.staging_buffers[vk_engine.current_frame as usize];
+        if staging_buffer.buffer.buffer.length < length {
+            let new_staging_buffer = Self::allocate_staging_buffer(vk_engine, length);
+            let old_staging_buffer = std::mem::replace(staging_buffer, new_staging_buffer);
+            cleanup_queue.queue(old_staging_buffer);
+        }
+
+        if required == 0 {
+            return reallocated;
+        }
+
+        staging_buffer.buffer.write(push_buffer.data(), 0);
+
+        // The device-local buffer might still be read by the previous frame.
+        vk_engine.device.cmd_pipeline_barrier(
+            command_buffer,
+            self.dst_stage_mask,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[],
+        );
+
+        let size = required * std::mem::size_of::<T>() as u64;
+        vk_engine.device.cmd_copy_buffer(
+            command_buffer,
+            staging_buffer.buffer.buffer.buffer,
+            self.buffer.buffer.buffer,
+            &[vk::BufferCopy {
+                src_offset: 0,
+                dst_offset: 0,
+                size,
+            }],
+        );
+
+        let buffer_barrier = vk::BufferMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(self.dst_access_mask)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .buffer(self.buffer.buffer.buffer)
+            .offset(0)
+            .size(size)
+            .build();
+
+        vk_engine.device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            self.dst_stage_mask,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[buffer_barrier],
+            &[],
+        );
+
+        reallocated
+    }
+
+    /// Frees the buffer and memory resources held by `self`.
+    /// # Safety
+    /// The buffers must not be used anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        for staging_buffer in &mut self.staging_buffers {
+            staging_buffer.destroy(device);
+        }
+        self.buffer.destroy(device);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_geometrically() {
+        assert_eq!(grown_length(4, 3), 4);
+        assert_eq!(grown_length(4, 5), 8);
+        assert_eq!(grown_length(4, 20), 20);
+        assert_eq!(grown_length(0, 0), 1);
+        assert_eq!(grown_length(0, 3), 3);
+        assert_eq!(grown_length(1, 2), 2);
+    }
+}
@@ -0,0 +1,591 @@
+//! Metadata of the core Vulkan formats: texel block size and extent, component count, aspects,
+//! depth-stencil classification and sRGB/UNORM counterparts.
+//! Also picks the first format of a list of candidates that the device supports for a use case.
+use ash::{vk, Instance};
+
+/// Which aspects images of a format have.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FormatKind {
+    Color,
+    Depth,
+    Stencil,
+    DepthStencil,
+}
+
+impl FormatKind {
+    /// Get the aspects of images with a format of this kind.
+    pub fn aspect_mask(self) -> vk::ImageAspectFlags {
+        match self {
+            FormatKind::Color => vk::ImageAspectFlags::COLOR,
+            FormatKind::Depth => vk::ImageAspectFlags::DEPTH,
+            FormatKind::Stencil => vk::ImageAspectFlags::STENCIL,
+            FormatKind::DepthStencil => vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL,
+        }
+    }
+}
+
+/// The layout of a format's texel blocks and its components.
+/// Uncompressed formats have 1x1 blocks; block-compressed formats have larger blocks.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FormatInfo {
+    /// Size in bytes of a texel block.
+    pub block_size: u32,
+    /// Width and height in texels of a texel block.
+    pub block_width: u32,
+    pub block_height: u32,
+    /// Number of components, e.g. 4 for RGBA formats and 2 for combined depth-stencil formats.
+    pub component_count: u32,
+    pub kind: FormatKind,
+}
+
+impl FormatInfo {
+    const fn texel(block_size: u32, component_count: u32) -> Self {
+        Self::block(1, 1, block_size, component_count)
+    }
+
+    const fn block(
+        block_width: u32,
+        block_height: u32,
+        block_size: u32,
+        component_count: u32,
+    ) -> Self {
+        Self {
+            block_size,
+            block_width,
+            block_height,
+            component_count,
+            kind: FormatKind::Color,
+        }
+    }
+
+    const fn depth_stencil(block_size: u32, component_count: u32, kind: FormatKind) -> Self {
+        Self {
+            kind,
+            ..Self::texel(block_size, component_count)
+        }
+    }
+
+    /// True iff the format is block-compressed.
+    pub fn is_compressed(&self) -> bool {
+        self.block_width > 1 || self.block_height > 1
+    }
+
+    /// Get the aspects of images with this format.
+    pub fn aspect_mask(&self) -> vk::ImageAspectFlags {
+        self.kind.aspect_mask()
+    }
+
+    /// Get the size in bytes of a tightly packed image with the given extent.
+    /// Partial blocks at the image edges count as whole blocks.
+    pub fn image_size(&self, extent: vk::Extent3D) -> u64 {
+        let blocks_x = (extent.width as u64).div_ceil(self.block_width as u64);
+        let blocks_y = (extent.height as u64).div_ceil(self.block_height as u64);
+        blocks_x * blocks_y * extent.depth as u64 * self.block_size as u64
+    }
+
+    /// Get the alignment of buffer offsets for copies to images of this format.
+    pub fn copy_alignment(&self) -> u64 {
+        // The least common multiple of the block size and 4, as required by vkCmdCopyBufferToImage.
+        match self.block_size % 4 {
+            0 => self.block_size as u64,
+            2 => self.block_size as u64 * 2,
+            _ => self.block_size as u64 * 4,
+        }
+    }
+
+    /// Get the layout of buffer data copied to or from a single aspect of images with this format.
+    /// Depth-stencil formats are copied one aspect at a time, with the depth and stencil data
+    /// tightly packed on their own, e.g. 4 bytes per texel for the depth aspect of `D24_UNORM_S8_UINT`.
+    /// Returns `None` if the format doesn't have the aspect.
+    pub fn aspect_copy_info(
+        &self,
+        format: vk::Format,
+        aspect: vk::ImageAspectFlags,
+    ) -> Option<Self> {
+        if !self.aspect_mask().contains(aspect) || aspect.as_raw().count_ones() != 1 {
+            return None;
+        }
+        let block_size = match (self.kind, aspect) {
+            (FormatKind::Color, _) => return Some(*self),
+            (_, vk::ImageAspectFlags::STENCIL) => 1,
+            _ => match format {
+                vk::Format::D16_UNORM | vk::Format::D16_UNORM_S8_UINT => 2,
+                _ => 4,
+            },
+        };
+        Some(Self {
+            block_size,
+            component_count: 1,
+            ..*self
+        })
+    }
+}
+
+/// Get the metadata of the given format, or `None` for formats that are not part of Vulkan 1.0
+/// and for `UNDEFINED`.
+pub fn format_info(format: vk::Format) -> Option<FormatInfo> {
+    use vk::Format as F;
+    use FormatKind::*;
+    let info = match format {
+        F::R4G4_UNORM_PACK8 => FormatInfo::texel(1, 2),
+        F::R4G4B4A4_UNORM_PACK16
+        | F::B4G4R4A4_UNORM_PACK16
+        | F::R5G5B5A1_UNORM_PACK16
+        | F::B5G5R5A1_UNORM_PACK16
+        | F::A1R5G5B5_UNORM_PACK16 => FormatInfo::texel(2, 4),
+        F::R5G6B5_UNORM_PACK16 | F::B5G6R5_UNORM_PACK16 => FormatInfo::texel(2, 3),
+
+        F::R8_UNORM
+        | F::R8_SNORM
+        | F::R8_USCALED
+        | F::R8_SSCALED
+        | F::R8_UINT
+        | F::R8_SINT
+        | F::R8_SRGB => FormatInfo::texel(1, 1),
+        F::R8G8_UNORM
+        | F::R8G8_SNORM
+        | F::R8G8_USCALED
+        | F::R8G8_SSCALED
+        | F::R8G8_UINT
+        | F::R8G8_SINT
+        | F::R8G8_SRGB => FormatInfo::texel(2, 2),
+        F::R8G8B8_UNORM
+        | F::R8G8B8_SNORM
+        | F::R8G8B8_USCALED
+        | F::R8G8B8_SSCALED
+        | F::R8G8B8_UINT
+        | F::R8G8B8_SINT
+        | F::R8G8B8_SRGB
+        | F::B8G8R8_UNORM
+        | F::B8G8R8_SNORM
+        | F::B8G8R8_USCALED
+        | F::B8G8R8_SSCALED
+        | F::B8G8R8_UINT
+        | F::B8G8R8_SINT
+        | F::B8G8R8_SRGB => FormatInfo::texel(3, 3),
+        F::R8G8B8A8_UNORM
+        | F::R8G8B8A8_SNORM
+        | F::R8G8B8A8_USCALED
+        | F::R8G8B8A8_SSCALED
+        | F::R8G8B8A8_UINT
+        | F::R8G8B8A8_SINT
+        | F::R8G8B8A8_SRGB
+        | F::B8G8R8A8_UNORM
+        | F::B8G8R8A8_SNORM
+        | F::B8G8R8A8_USCALED
+        | F::B8G8R8A8_SSCALED
+        | F::B8G8R8A8_UINT
+        | F::B8G8R8A8_SINT
+        | F::B8G8R8A8_SRGB
+        | F::A8B8G8R8_UNORM_PACK32
+        | F::A8B8G8R8_SNORM_PACK32
+        | F::A8B8G8R8_USCALED_PACK32
+        | F::A8B8G8R8_SSCALED_PACK32
+        | F::A8B8G8R8_UINT_PACK32
+        | F::A8B8G8R8_SINT_PACK32
+        | F::A8B8G8R8_SRGB_PACK32
+        | F::A2R10G10B10_UNORM_PACK32
+        | F::A2R10G10B10_SNORM_PACK32
+        | F::A2R10G10B10_USCALED_PACK32
+        | F::A2R10G10B10_SSCALED_PACK32
+        | F::A2R10G10B10_UINT_PACK32
+        | F::A2R10G10B10_SINT_PACK32
+        | F::A2B10G10R10_UNORM_PACK32
+        | F::A2B10G10R10_SNORM_PACK32
+        | F::A2B10G10R10_USCALED_PACK32
+        | F::A2B10G10R10_SSCALED_PACK32
+        | F::A2B10G10R10_UINT_PACK32
+        | F::A2B10G10R10_SINT_PACK32 => FormatInfo::texel(4, 4),
+
+        F::R16_UNORM
+        | F::R16_SNORM
+        | F::R16_USCALED
+        | F::R16_SSCALED
+        | F::R16_UINT
+        | F::R16_SINT
+        | F::R16_SFLOAT => FormatInfo::texel(2, 1),
+        F::R16G16_UNORM
+        | F::R16G16_SNORM
+        | F::R16G16_USCALED
+        | F::R16G16_SSCALED
+        | F::R16G16_UINT
+        | F::R16G16_SINT
+        | F::R16G16_SFLOAT => FormatInfo::texel(4, 2),
+        F::R16G16B16_UNORM
+        | F::R16G16B16_SNORM
+        | F::R16G16B16_USCALED
+        | F::R16G16B16_SSCALED
+        | F::R16G16B16_UINT
+        | F::R16G16B16_SINT
+        | F::R16G16B16_SFLOAT => FormatInfo::texel(6, 3),
+        F::R16G16B16A16_UNORM
+        | F::R16G16B16A16_SNORM
+        | F::R16G16B16A16_USCALED
+        | F::R16G16B16A16_SSCALED
+        | F::R16G16B16A16_UINT
+        | F::R16G16B16A16_SINT
+        | F::R16G16B16A16_SFLOAT => FormatInfo::texel(8, 4),
+
+        F::R32_UINT | F::R32_SINT | F::R32_SFLOAT => FormatInfo::texel(4, 1),
+        F::R32G32_UINT | F::R32G32_SINT | F::R32G32_SFLOAT => FormatInfo::texel(8, 2),
+        F::R32G32B32_UINT | F::R32G32B32_SINT | F::R32G32B32_SFLOAT => FormatInfo::texel(12, 3),
+        F::R32G32B32A32_UINT | F::R32G32B32A32_SINT | F::R32G32B32A32_SFLOAT => {
+            FormatInfo::texel(16, 4)
+        }
+        F::R64_UINT | F::R64_SINT | F::R64_SFLOAT => FormatInfo::texel(8, 1),
+        F::R64G64_UINT | F::R64G64_SINT | F::R64G64_SFLOAT => FormatInfo::texel(16, 2),
+        F::R64G64B64_UINT | F::R64G64B64_SINT | F::R64G64B64_SFLOAT => FormatInfo::texel(24, 3),
+        F::R64G64B64A64_UINT | F::R64G64B64A64_SINT | F::R64G64B64A64_SFLOAT => {
+            FormatInfo::texel(32, 4)
+        }
+        F::B10G11R11_UFLOAT_PACK32 | F::E5B9G9R9_UFLOAT_PACK32 => FormatInfo::texel(4, 3),
+
+        F::D16_UNORM => FormatInfo::depth_stencil(2, 1, Depth),
+        F::X8_D24_UNORM_PACK32 | F::D32_SFLOAT => FormatInfo::depth_stencil(4, 1, Depth),
+        F::S8_UINT => FormatInfo::depth_stencil(1, 1, Stencil),
+        F::D16_UNORM_S8_UINT => FormatInfo::depth_stencil(3, 2, DepthStencil),
+        F::D24_UNORM_S8_UINT => FormatInfo::depth_stencil(4, 2, DepthStencil),
+        F::D32_SFLOAT_S8_UINT => FormatInfo::depth_stencil(5, 2, DepthStencil),
+
+        F::BC1_RGB_UNORM_BLOCK
+        | F::BC1_RGB_SRGB_BLOCK
+        | F::ETC2_R8G8B8_UNORM_BLOCK
+        | F::ETC2_R8G8B8_SRGB_BLOCK => FormatInfo::block(4, 4, 8, 3),
+        F::BC1_RGBA_UNORM_BLOCK
+        | F::BC1_RGBA_SRGB_BLOCK
+        | F::ETC2_R8G8B8A1_UNORM_BLOCK
+        | F::ETC2_R8G8B8A1_SRGB_BLOCK => FormatInfo::block(4, 4, 8, 4),
+        F::BC4_UNORM_BLOCK
+        | F::BC4_SNORM_BLOCK
+        | F::EAC_R11_UNORM_BLOCK
+        | F::EAC_R11_SNORM_BLOCK => FormatInfo::block(4, 4, 8, 1),
+        F::BC5_UNORM_BLOCK
+        | F::BC5_SNORM_BLOCK
+        | F::EAC_R11G11_UNORM_BLOCK
+        | F::EAC_R11G11_SNORM_BLOCK => FormatInfo::block(4, 4, 16, 2),
+        F::BC6H_UFLOAT_BLOCK | F::BC6H_SFLOAT_BLOCK => FormatInfo::block(4, 4, 16, 3),
+        F::BC2_UNORM_BLOCK
+        | F::BC2_SRGB_BLOCK
+        | F::BC3_UNORM_BLOCK
+        | F::BC3_SRGB_BLOCK
+        | F::BC7_UNORM_BLOCK
+        | F::BC7_SRGB_BLOCK
+        | F::ETC2_R8G8B8A8_UNORM_BLOCK
+        | F::ETC2_R8G8B8A8_SRGB_BLOCK
+        | F::ASTC_4X4_UNORM_BLOCK
+        | F::ASTC_4X4_SRGB_BLOCK => FormatInfo::block(4, 4, 16, 4),
+        F::ASTC_5X4_UNORM_BLOCK | F::ASTC_5X4_SRGB_BLOCK => FormatInfo::block(5, 4, 16, 4),
+        F::ASTC_5X5_UNORM_BLOCK | F::ASTC_5X5_SRGB_BLOCK => FormatInfo::block(5, 5, 16, 4),
+        F::ASTC_6X5_UNORM_BLOCK | F::ASTC_6X5_SRGB_BLOCK => FormatInfo::block(6, 5, 16, 4),
+        F::ASTC_6X6_UNORM_BLOCK | F::ASTC_6X6_SRGB_BLOCK => FormatInfo::block(6, 6, 16, 4),
+        F::ASTC_8X5_UNORM_BLOCK | F::ASTC_8X5_SRGB_BLOCK => FormatInfo::block(8, 5, 16, 4),
+        F::ASTC_8X6_UNORM_BLOCK | F::ASTC_8X6_SRGB_BLOCK => FormatInfo::block(8, 6, 16, 4),
+        F::ASTC_8X8_UNORM_BLOCK | F::ASTC_8X8_SRGB_BLOCK => FormatInfo::block(8, 8, 16, 4),
+        F::ASTC_10X5_UNORM_BLOCK | F::ASTC_10X5_SRGB_BLOCK => FormatInfo::block(10, 5, 16, 4),
+        F::ASTC_10X6_UNORM_BLOCK | F::ASTC_10X6_SRGB_BLOCK => FormatInfo::block(10, 6, 16, 4),
+        F::ASTC_10X8_UNORM_BLOCK | F::ASTC_10X8_SRGB_BLOCK => FormatInfo::block(10, 8, 16, 4),
+        F::ASTC_10X10_UNORM_BLOCK | F::ASTC_10X10_SRGB_BLOCK => FormatInfo::block(10, 10, 16, 4),
+        F::ASTC_12X10_UNORM_BLOCK | F::ASTC_12X10_SRGB_BLOCK => FormatInfo::block(12, 10, 16, 4),
+        F::ASTC_12X12_UNORM_BLOCK | F::ASTC_12X12_SRGB_BLOCK => FormatInfo::block(12, 12, 16, 4),
+        _ => return None,
+    };
+    Some(info)
+}
+
+/// Get the aspects of images with the given format: depth and/or stencil for depth-stencil formats,
+/// colour for all other formats.
+pub fn aspect_mask(format: vk::Format) -> vk::ImageAspectFlags {
+    format_info(format).map_or(vk::ImageAspectFlags::COLOR, |info| info.aspect_mask())
+}
+
+/// Pairs of UNORM formats and their sRGB counterparts.
+const SRGB_PAIRS: [(vk::Format, vk::Format); 29] = {
+    use vk::Format as F;
+    [
+        (F::R8_UNORM, F::R8_SRGB),
+        (F::R8G8_UNORM, F::R8G8_SRGB),
+        (F::R8G8B8_UNORM, F::R8G8B8_SRGB),
+        (F::B8G8R8_UNORM, F::B8G8R8_SRGB),
+        (F::R8G8B8A8_UNORM, F::R8G8B8A8_SRGB),
+        (F::B8G8R8A8_UNORM, F::B8G8R8A8_SRGB),
+        (F::A8B8G8R8_UNORM_PACK32, F::A8B8G8R8_SRGB_PACK32),
+        (F::BC1_RGB_UNORM_BLOCK, F::BC1_RGB_SRGB_BLOCK),
+        (F::BC1_RGBA_UNORM_BLOCK, F::BC1_RGBA_SRGB_BLOCK),
+        (F::BC2_UNORM_BLOCK, F::BC2_SRGB_BLOCK),
+        (F::BC3_UNORM_BLOCK, F::BC3_SRGB_BLOCK),
+        (F::BC7_UNORM_BLOCK, F::BC7_SRGB_BLOCK),
+        (F::ETC2_R8G8B8_UNORM_BLOCK, F::ETC2_R8G8B8_SRGB_BLOCK),
+        (F::ETC2_R8G8B8A1_UNORM_BLOCK, F::ETC2_R8G8B8A1_SRGB_BLOCK),
+        (F::ETC2_R8G8B8A8_UNORM_BLOCK, F::ETC2_R8G8B8A8_SRGB_BLOCK),
+        (F::ASTC_4X4_UNORM_BLOCK, F::ASTC_4X4_SRGB_BLOCK),
+        (F::ASTC_5X4_UNORM_BLOCK, F::ASTC_5X4_SRGB_BLOCK),
+        (F::ASTC_5X5_UNORM_BLOCK, F::ASTC_5X5_SRGB_BLOCK),
+        (F::ASTC_6X5_UNORM_BLOCK, F::ASTC_6X5_SRGB_BLOCK),
+        (F::ASTC_6X6_UNORM_BLOCK, F::ASTC_6X6_SRGB_BLOCK),
+        (F::ASTC_8X5_UNORM_BLOCK, F::ASTC_8X5_SRGB_BLOCK),
+        (F::ASTC_8X6_UNORM_BLOCK, F::ASTC_8X6_SRGB_BLOCK),
+        (F::ASTC_8X8_UNORM_BLOCK, F::ASTC_8X8_SRGB_BLOCK),
+        (F::ASTC_10X5_UNORM_BLOCK, F::ASTC_10X5_SRGB_BLOCK),
+        (F::ASTC_10X6_UNORM_BLOCK, F::ASTC_10X6_SRGB_BLOCK),
+        (F::ASTC_10X8_UNORM_BLOCK, F::ASTC_10X8_SRGB_BLOCK),
+        (F::ASTC_10X10_UNORM_BLOCK, F::ASTC_10X10_SRGB_BLOCK),
+        (F::ASTC_12X10_UNORM_BLOCK, F::ASTC_12X10_SRGB_BLOCK),
+        (F::ASTC_12X12_UNORM_BLOCK, F::ASTC_12X12_SRGB_BLOCK),
+    ]
+};
+
+/// Get the sRGB counterpart of a UNORM format, e.g. `R8G8B8A8_SRGB` for `R8G8B8A8_UNORM`.
+/// Returns `None` if the format has no sRGB counterpart.
+pub fn srgb_format(format: vk::Format) -> Option<vk::Format> {
+    SRGB_PAIRS
+        .iter()
+        .find(|(unorm, _)| *unorm == format)
+        .map(|&(_, srgb)| srgb)
+}
+
+/// Get the UNORM counterpart of an sRGB format, e.g. `R8G8B8A8_UNORM` for `R8G8B8A8_SRGB`.
+/// Returns `None` if the format is not an sRGB format.
+pub fn unorm_format(format: vk::Format) -> Option<vk::Format> {
+    SRGB_PAIRS
+        .iter()
+        .find(|(_, srgb)| *srgb == format)
+        .map(|&(unorm, _)| unorm)
+}
+
+/// True iff the format stores sRGB-encoded colour values.
+pub fn is_srgb(format: vk::Format) -> bool {
+    unorm_format(format).is_some()
+}
+
+/// Check whether `properties` report all `features` for images with the given tiling.
+/// Returns false for tilings other than `LINEAR` and `OPTIMAL`.
+pub fn has_features(
+    properties: &vk::FormatProperties,
+    tiling: vk::ImageTiling,
+    features: vk::FormatFeatureFlags,
+) -> bool {
+    match tiling {
+        vk::ImageTiling::LINEAR => properties.linear_tiling_features.contains(features),
+        vk::ImageTiling::OPTIMAL => properties.optimal_tiling_features.contains(features),
+        _ => false,
+    }
+}
+
+/// Returns the first of `candidates` that supports all `features` with the given tiling on the device,
+/// or `None` if no candidate does. See `FormatRequirements` for candidate lists of common use cases.
+/// # Safety
+/// Instance and physical device must be valid.
+pub unsafe fn choose_supported_format(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    candidates: &[vk::Format],
+    tiling: vk::ImageTiling,
+    features: vk::FormatFeatureFlags,
+) -> Option<vk::Format> {
+    first_supported(candidates, tiling, features, |format| {
+        instance.get_physical_device_format_properties(physical_device, format)
+    })
+}
+
+fn first_supported(
+    candidates: &[vk::Format],
+    tiling: vk::ImageTiling,
+    features: vk::FormatFeatureFlags,
+    properties: impl Fn(vk::Format) -> vk::FormatProperties,
+) -> Option<vk::Format> {
+    candidates
+        .iter()
+        .copied()
+        .find(|&format| has_features(&properties(format), tiling, features))
+}
+
+/// Candidate formats for a use case, in order of preference, together with the features the use case requires.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FormatRequirements {
+    pub candidates: &'static [vk::Format],
+    pub tiling: vk::ImageTiling,
+    pub features: vk::FormatFeatureFlags,
+}
+
+impl FormatRequirements {
+    /// Depth attachments, preferring 32-bit float depth. Formats with a stencil aspect are only
+    /// picked if the device supports no pure depth format besides `D16_UNORM`.
+    pub const DEPTH: Self = Self {
+        candidates: &[
+            vk::Format::D32_SFLOAT,
+            vk::Format::X8_D24_UNORM_PACK32,
+            vk::Format::D32_SFLOAT_S8_UINT,
+            vk::Format::D24_UNORM_S8_UINT,
+            vk::Format::D16_UNORM,
+        ],
+        tiling: vk::ImageTiling::OPTIMAL,
+        features: vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+    };
+
+    /// Depth-stencil attachments.
+    pub const DEPTH_STENCIL: Self = Self {
+        candidates: &[
+            vk::Format::D24_UNORM_S8_UINT,
+            vk::Format::D32_SFLOAT_S8_UINT,
+            vk::Format::D16_UNORM_S8_UINT,
+        ],
+        tiling: vk::ImageTiling::OPTIMAL,
+        features: vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+    };
+
+    /// HDR colour targets that are blended into and sampled with linear filtering afterwards,
+    /// e.g. by a tone mapping pass. Falls back to `B10G11R11_UFLOAT_PACK32`, which has no alpha.
+    pub const HDR_COLOR_TARGET: Self = Self {
+        candidates: &[
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::Format::B10G11R11_UFLOAT_PACK32,
+        ],
+        tiling: vk::ImageTiling::OPTIMAL,
+        features: vk::FormatFeatureFlags::from_raw(
+            vk::FormatFeatureFlags::COLOR_ATTACHMENT.as_raw()
+                | vk::FormatFeatureFlags::COLOR_ATTACHMENT_BLEND.as_raw()
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE.as_raw()
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR.as_raw(),
+        ),
+    };
+
+    /// RGBA images written by compute shaders and sampled afterwards.
+    pub const STORAGE_IMAGE: Self = Self {
+        candidates: &[
+            vk::Format::R8G8B8A8_UNORM,
+            vk::Format::R16G16B16A16_SFLOAT,
+            vk::Format::R32G32B32A32_SFLOAT,
+        ],
+        tiling: vk::ImageTiling::OPTIMAL,
+        features: vk::FormatFeatureFlags::from_raw(
+            vk::FormatFeatureFlags::STORAGE_IMAGE.as_raw()
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE.as_raw(),
+        ),
+    };
+
+    /// 8-bit sRGB textures sampled with linear filtering. The candidates differ in channel order,
+    /// so the texel data has to match the chosen format.
+    pub const SRGB_TEXTURE: Self = Self {
+        candidates: &[vk::Format::R8G8B8A8_SRGB, vk::Format::B8G8R8A8_SRGB],
+        tiling: vk::ImageTiling::OPTIMAL,
+        features: vk::FormatFeatureFlags::from_raw(
+            vk::FormatFeatureFlags::SAMPLED_IMAGE.as_raw()
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR.as_raw(),
+        ),
+    };
+
+    /// Returns the first candidate the device supports; see `choose_supported_format`.
+    /// # Safety
+    /// Instance and physical device must be valid.
+    pub unsafe fn choose(
+        &self,
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> Option<vk::Format> {
+        choose_supported_format(
+            instance,
+            physical_device,
+            self.candidates,
+            self.tiling,
+            self.features,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_blocks_round_up() {
+        let bc1 = format_info(vk::Format::BC1_RGBA_UNORM_BLOCK).unwrap();
+        let extent = vk::Extent3D {
+            width: 6,
+            height: 2,
+            depth: 1,
+        };
+        assert!(bc1.is_compressed());
+        assert_eq!(bc1.image_size(extent), 2 * 8);
+        assert_eq!(bc1.copy_alignment(), 8);
+
+        let rgb8 = format_info(vk::Format::R8G8B8_UNORM).unwrap();
+        assert_eq!(rgb8.image_size(extent), 36);
+        assert_eq!(rgb8.copy_alignment(), 12);
+    }
+
+    #[test]
+    fn depth_stencil_aspects() {
+        let format = vk::Format::D32_SFLOAT_S8_UINT;
+        let info = format_info(format).unwrap();
+        assert_eq!(info.kind, FormatKind::DepthStencil);
+        assert_eq!(info.block_size, 5);
+        let depth = info
+            .aspect_copy_info(format, vk::ImageAspectFlags::DEPTH)
+            .unwrap();
+        assert_eq!(depth.block_size, 4);
+        let stencil = info
+            .aspect_copy_info(format, vk::ImageAspectFlags::STENCIL)
+            .unwrap();
+        assert_eq!(stencil.block_size, 1);
+        assert!(info
+            .aspect_copy_info(format, vk::ImageAspectFlags::COLOR)
+            .is_none());
+        assert_eq!(
+            aspect_mask(vk::Format::S8_UINT),
+            vk::ImageAspectFlags::STENCIL
+        );
+    }
+
+    #[test]
+    fn srgb_pairs() {
+        assert_eq!(
+            srgb_format(vk::Format::BC7_UNORM_BLOCK),
+            Some(vk::Format::BC7_SRGB_BLOCK)
+        );
+        assert_eq!(
+            unorm_format(vk::Format::B8G8R8A8_SRGB),
+            Some(vk::Format::B8G8R8A8_UNORM)
+        );
+        assert_eq!(srgb_format(vk::Format::R16_UNORM), None);
+        assert!(!is_srgb(vk::Format::R8G8B8A8_UNORM));
+    }
+
+    #[test]
+    fn first_supported_candidate() {
+        let properties = |format| match format {
+            vk::Format::R16G16B16A16_SFLOAT => vk::FormatProperties {
+                linear_tiling_features: FormatRequirements::HDR_COLOR_TARGET.features,
+                optimal_tiling_features: vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+                ..Default::default()
+            },
+            _ => vk::FormatProperties {
+                optimal_tiling_features: FormatRequirements::HDR_COLOR_TARGET.features
+                    | vk::FormatFeatureFlags::TRANSFER_DST,
+                ..Default::default()
+            },
+        };
+        let hdr = FormatRequirements::HDR_COLOR_TARGET;
+        assert_eq!(
+            first_supported(hdr.candidates, hdr.tiling, hdr.features, properties),
+            Some(vk::Format::B10G11R11_UFLOAT_PACK32)
+        );
+        assert_eq!(
+            first_supported(
+                hdr.candidates,
+                vk::ImageTiling::LINEAR,
+                hdr.features,
+                properties
+            ),
+            Some(vk::Format::R16G16B16A16_SFLOAT)
+        );
+        assert_eq!(
+            first_supported(
+                hdr.candidates,
+                hdr.tiling,
+                vk::FormatFeatureFlags::STORAGE_IMAGE,
+                properties
+            ),
+            None
+        );
+    }
+}
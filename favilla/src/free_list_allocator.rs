@@ -0,0 +1,452 @@
+use crate::allocator::{get_aligned_offset, supports_memory_type, Allocator};
+use ash::vk::{DeviceMemory, DeviceSize, MemoryRequirements};
+use ash::{vk, Device};
+use std::ffi::c_void;
+use std::ptr::NonNull;
+
+pub use crate::allocator::{SubAllocation, SubAllocationError};
+
+/// The free ranges of a memory block, sorted by offset. Adjacent free ranges are always coalesced.
+#[derive(Debug)]
+struct FreeList {
+    ranges: Vec<(DeviceSize, DeviceSize)>,
+}
+
+impl FreeList {
+    fn new(size: DeviceSize) -> Self {
+        Self {
+            ranges: vec![(0, size)],
+        }
+    }
+
+    /// Takes the first range that fits `size` bytes at the given alignment and returns its aligned offset.
+    /// Padding before the aligned offset stays free.
+    fn allocate(&mut self, size: DeviceSize, alignment: DeviceSize) -> Option<DeviceSize> {
+        let (index, offset) =
+            self.ranges
+                .iter()
+                .enumerate()
+                .find_map(|(index, &(start, range_size))| {
+                    let offset = get_aligned_offset(start, alignment);
+                    (offset + size <= start + range_size).then_some((index, offset))
+                })?;
+
+        let (start, range_size) = self.ranges[index];
+        let end = start + range_size;
+        let mut remaining = Vec::with_capacity(2);
+        if offset > start {
+            remaining.push((start, offset - start));
+        }
+        if offset + size < end {
+            remaining.push((offset + size, end - offset - size));
+        }
+        self.ranges.splice(index..=index, remaining);
+        Some(offset)
+    }
+
+    /// Returns the range to the free list, merging it with adjacent free ranges.
+    fn free(&mut self, offset: DeviceSize, size: DeviceSize) {
+        let index = self.ranges.partition_point(|&(start, _)| start < offset);
+        self.ranges.insert(index, (offset, size));
+        if let Some(&(next_start, next_size)) = self.ranges.get(index + 1) {
+            if offset + size == next_start {
+                self.ranges[index].1 += next_size;
+                self.ranges.remove(index + 1);
+            }
+        }
+        if index > 0 {
+            let (previous_start, previous_size) = self.ranges[index - 1];
+            if previous_start + previous_size == offset {
+                self.ranges[index - 1].1 += self.ranges[index].1;
+                self.ranges.remove(index);
+            }
+        }
+    }
+
+    fn free_size(&self) -> DeviceSize {
+        self.ranges.iter().map(|&(_, size)| size).sum()
+    }
+
+    fn largest_free_range(&self) -> DeviceSize {
+        self.ranges.iter().map(|&(_, size)| size).max().unwrap_or(0)
+    }
+}
+
+#[derive(Debug)]
+struct MemoryBlock {
+    memory: DeviceMemory,
+    size: DeviceSize,
+    mapped_ptr: Option<NonNull<c_void>>,
+    free_list: FreeList,
+    allocation_count: usize,
+}
+
+impl MemoryBlock {
+    fn new(memory: DeviceMemory, size: DeviceSize, mapped_ptr: Option<NonNull<c_void>>) -> Self {
+        Self {
+            memory,
+            size,
+            mapped_ptr,
+            free_list: FreeList::new(size),
+            allocation_count: 0,
+        }
+    }
+
+    /// Takes the first free range of the block that fits `memory_req`.
+    unsafe fn allocate(&mut self, memory_req: MemoryRequirements) -> Option<SubAllocation> {
+        let offset = self
+            .free_list
+            .allocate(memory_req.size, memory_req.alignment)?;
+        self.allocation_count += 1;
+        Some(SubAllocation {
+            memory: self.memory,
+            offset,
+            size: memory_req.size,
+            mapped_ptr: self
+                .mapped_ptr
+                .map(|ptr| NonNull::new_unchecked(ptr.as_ptr().add(offset as usize))),
+        })
+    }
+}
+
+/// Statistics about the memory of a `FreeListAllocator`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct AllocatorStatistics {
+    /// The number of device memory blocks.
+    pub block_count: usize,
+    /// The number of live sub-allocations.
+    pub allocation_count: usize,
+    /// The total size of all blocks.
+    pub block_size: DeviceSize,
+    /// The size of all live sub-allocations, excluding alignment padding.
+    pub allocated_size: DeviceSize,
+    /// The number of free ranges; many small free ranges indicate fragmentation.
+    pub free_range_count: usize,
+    /// The size of the largest free range.
+    pub largest_free_range: DeviceSize,
+}
+
+/// A general-purpose allocator that sub-allocates from device memory blocks and supports freeing
+/// individual allocations. Free ranges are kept in a first-fit free list per block and coalesced on free.
+/// New blocks are allocated on demand; requests larger than the block size get a block of their own.
+/// Like `LinearAllocator`, the allocator does not account for `bufferImageGranularity`,
+/// so linear and optimal resources should use separate allocators.
+pub struct FreeListAllocator {
+    memory_type_index: u32,
+    allocate_flags: vk::MemoryAllocateFlags,
+    block_size: DeviceSize,
+    mapped: bool,
+    blocks: Vec<MemoryBlock>,
+}
+
+// See `SubAllocation`: the mapped pointers are only handed out, never dereferenced by the allocator.
+unsafe impl Send for FreeListAllocator {}
+
+impl FreeListAllocator {
+    /// Create a new allocator that allocates blocks of `block_size` bytes with the given memory type index.
+    /// No memory is allocated until the first allocation.
+    pub fn new(block_size: DeviceSize, memory_type_index: u32) -> Self {
+        Self::with_allocate_flags(
+            block_size,
+            memory_type_index,
+            vk::MemoryAllocateFlags::empty(),
+        )
+    }
+
+    /// Create a new allocator whose blocks are allocated with the given flags.
+    /// Use `DEVICE_ADDRESS` to sub-allocate buffers with `SHADER_DEVICE_ADDRESS` usage.
+    pub fn with_allocate_flags(
+        block_size: DeviceSize,
+        memory_type_index: u32,
+        allocate_flags: vk::MemoryAllocateFlags,
+    ) -> Self {
+        Self {
+            memory_type_index,
+            allocate_flags,
+            block_size,
+            mapped: false,
+            blocks: vec![],
+        }
+    }
+
+    /// Persistently maps all blocks, including blocks allocated later. Allocations made afterwards
+    /// carry a host pointer, which allows creating staging buffers from them.
+    /// If a block fails to be mapped, the blocks mapped before it are unmapped again.
+    /// # Safety
+    /// The memory type has to be host visible. The blocks must not be mapped already.
+    pub unsafe fn map(&mut self, device: &Device) -> Result<(), vk::Result> {
+        for index in 0..self.blocks.len() {
+            match device.map_memory(
+                self.blocks[index].memory,
+                0,
+                vk::WHOLE_SIZE,
+                vk::MemoryMapFlags::empty(),
+            ) {
+                Ok(ptr) => self.blocks[index].mapped_ptr = NonNull::new(ptr),
+                Err(e) => {
+                    for block in &mut self.blocks[..index] {
+                        device.unmap_memory(block.memory);
+                        block.mapped_ptr = None;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        self.mapped = true;
+        Ok(())
+    }
+
+    /// Try to get a free chunk of memory from the allocator, allocating a new block if no block has
+    /// a large enough free range.
+    /// # Safety
+    /// Requires a valid device with sufficient memory; all blocks must have been allocated from it.
+    pub unsafe fn allocate(
+        &mut self,
+        device: &Device,
+        memory_req: MemoryRequirements,
+    ) -> Result<SubAllocation, SubAllocationError> {
+        if !supports_memory_type(&memory_req, self.memory_type_index) {
+            return Err(SubAllocationError::IncompatibleMemoryType(
+                self.memory_type_index,
+            ));
+        }
+        if let Some(allocation) = self.allocate_from_blocks(memory_req) {
+            return Ok(allocation);
+        }
+
+        let block = self.allocate_block(device, self.new_block_size(memory_req.size))?;
+        self.blocks.push(block);
+        self.blocks
+            .last_mut()
+            .unwrap()
+            .allocate(memory_req)
+            .ok_or(SubAllocationError::OutOfMemory)
+    }
+
+    /// Takes the first free range of any existing block that fits `memory_req`.
+    unsafe fn allocate_from_blocks(
+        &mut self,
+        memory_req: MemoryRequirements,
+    ) -> Option<SubAllocation> {
+        self.blocks
+            .iter_mut()
+            .find_map(|block| block.allocate(memory_req))
+    }
+
+    /// Get the size of a new block for a request of `size` bytes: the block size, unless the request is larger.
+    fn new_block_size(&self, size: DeviceSize) -> DeviceSize {
+        size.max(self.block_size)
+    }
+
+    unsafe fn allocate_block(
+        &self,
+        device: &Device,
+        size: DeviceSize,
+    ) -> Result<MemoryBlock, SubAllocationError> {
+        let mut allocate_flags_info =
+            vk::MemoryAllocateFlagsInfo::builder().flags(self.allocate_flags);
+        let mut allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(size)
+            .memory_type_index(self.memory_type_index);
+        if !self.allocate_flags.is_empty() {
+            allocate_info = allocate_info.push_next(&mut allocate_flags_info);
+        }
+        let memory = device
+            .allocate_memory(&allocate_info, None)
+            .map_err(|_| SubAllocationError::OutOfMemory)?;
+
+        let mapped_ptr = if self.mapped {
+            match device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) {
+                Ok(ptr) => NonNull::new(ptr),
+                Err(_) => {
+                    device.free_memory(memory, None);
+                    return Err(SubAllocationError::NotMapped);
+                }
+            }
+        } else {
+            None
+        };
+
+        Ok(MemoryBlock::new(memory, size, mapped_ptr))
+    }
+
+    /// Returns the memory of `allocation` to the allocator. The block's memory stays allocated;
+    /// see `release_empty_blocks`. Allocations not made by this allocator are ignored.
+    /// # Safety
+    /// The allocation must not be used anymore.
+    pub unsafe fn free(&mut self, allocation: SubAllocation) {
+        debug_assert!(
+            self.owns(&allocation),
+            "The allocation was not made by this allocator"
+        );
+        if let Some(block) = self
+            .blocks
+            .iter_mut()
+            .find(|block| block.memory == allocation.memory)
+        {
+            block.free_list.free(allocation.offset, allocation.size);
+            block.allocation_count -= 1;
+        }
+    }
+
+    /// Frees the device memory of all blocks without live allocations.
+    /// # Safety
+    /// Memory of freed allocations must not be used anymore.
+    pub unsafe fn release_empty_blocks(&mut self, device: &Device) {
+        self.blocks.retain(|block| {
+            let empty = block.allocation_count == 0;
+            if empty {
+                device.free_memory(block.memory, None);
+            }
+            !empty
+        });
+    }
+
+    /// Get statistics about the allocator's blocks and allocations.
+    pub fn statistics(&self) -> AllocatorStatistics {
+        self.blocks
+            .iter()
+            .fold(AllocatorStatistics::default(), |stats, block| {
+                AllocatorStatistics {
+                    block_count: stats.block_count + 1,
+                    allocation_count: stats.allocation_count + block.allocation_count,
+                    block_size: stats.block_size + block.size,
+                    allocated_size: stats.allocated_size + block.size - block.free_list.free_size(),
+                    free_range_count: stats.free_range_count + block.free_list.ranges.len(),
+                    largest_free_range: stats
+                        .largest_free_range
+                        .max(block.free_list.largest_free_range()),
+                }
+            })
+    }
+
+    /// Frees the memory of all blocks.
+    /// # Safety
+    /// Memory must not be used anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        for block in self.blocks.drain(..) {
+            device.free_memory(block.memory, None);
+        }
+    }
+}
+
+impl Allocator for FreeListAllocator {
+    fn memory_type_index(&self) -> u32 {
+        self.memory_type_index
+    }
+
+    fn allocate_flags(&self) -> vk::MemoryAllocateFlags {
+        self.allocate_flags
+    }
+
+    fn is_mapped(&self) -> bool {
+        self.mapped
+    }
+
+    fn owns(&self, allocation: &SubAllocation) -> bool {
+        self.blocks
+            .iter()
+            .any(|block| block.memory == allocation.memory)
+    }
+
+    unsafe fn allocate(
+        &mut self,
+        device: &Device,
+        memory_req: MemoryRequirements,
+    ) -> Result<SubAllocation, SubAllocationError> {
+        FreeListAllocator::allocate(self, device, memory_req)
+    }
+
+    unsafe fn free(&mut self, allocation: SubAllocation) {
+        FreeListAllocator::free(self, allocation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    #[test]
+    fn free_list_coalescing() {
+        let mut free_list = FreeList::new(256);
+        assert_eq!(free_list.allocate(10, 1), Some(0));
+        assert_eq!(free_list.allocate(64, 64), Some(64));
+        // The padding between both allocations stays free.
+        assert_eq!(free_list.ranges, [(10, 54), (128, 128)]);
+        assert_eq!(free_list.allocate(32, 16), Some(16));
+        assert_eq!(free_list.allocate(200, 1), None);
+
+        free_list.free(64, 64);
+        assert_eq!(free_list.ranges, [(10, 6), (48, 208)]);
+        free_list.free(0, 10);
+        assert_eq!(free_list.ranges, [(0, 16), (48, 208)]);
+        free_list.free(16, 32);
+        assert_eq!(free_list.ranges, [(0, 256)]);
+        assert_eq!(free_list.free_size(), 256);
+    }
+
+    fn requirements(size: DeviceSize) -> MemoryRequirements {
+        MemoryRequirements {
+            size,
+            alignment: 16,
+            memory_type_bits: 1,
+        }
+    }
+
+    #[test]
+    fn oversized_requests_and_statistics() {
+        let mut allocator = FreeListAllocator::new(256, 0);
+        allocator
+            .blocks
+            .push(MemoryBlock::new(DeviceMemory::from_raw(1), 256, None));
+        unsafe {
+            let first = allocator.allocate_from_blocks(requirements(100)).unwrap();
+            assert_eq!(first.offset, 0);
+            assert_eq!(
+                allocator
+                    .allocate_from_blocks(requirements(100))
+                    .unwrap()
+                    .offset,
+                112
+            );
+
+            // A request larger than the block size doesn't fit any block and gets a block of its own.
+            assert!(allocator.allocate_from_blocks(requirements(1024)).is_none());
+            assert_eq!(allocator.new_block_size(1024), 1024);
+            assert_eq!(allocator.new_block_size(100), 256);
+            allocator
+                .blocks
+                .push(MemoryBlock::new(DeviceMemory::from_raw(2), 1024, None));
+            let large = allocator.allocate_from_blocks(requirements(1024)).unwrap();
+            assert_eq!(large.memory, DeviceMemory::from_raw(2));
+            assert_eq!(large.offset, 0);
+
+            assert_eq!(
+                allocator.statistics(),
+                AllocatorStatistics {
+                    block_count: 2,
+                    allocation_count: 3,
+                    block_size: 1280,
+                    allocated_size: 1224,
+                    free_range_count: 2,
+                    largest_free_range: 44,
+                }
+            );
+
+            allocator.free(first);
+            allocator.free(large);
+            assert_eq!(
+                allocator.statistics(),
+                AllocatorStatistics {
+                    block_count: 2,
+                    allocation_count: 1,
+                    block_size: 1280,
+                    allocated_size: 100,
+                    free_range_count: 3,
+                    largest_free_range: 1024,
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::buffer::{VulkanBuffer, VulkanBufferWithDedicatedAllocation};
+use crate::vk_engine::VulkanEngine;
+use ash::{vk, Device};
+
+/// Element types that can be used in index buffers.
+pub trait IndexElement: Copy {
+    /// The index type to use when binding a buffer with this element type.
+    const INDEX_TYPE: vk::IndexType;
+}
+
+impl IndexElement for u16 {
+    const INDEX_TYPE: vk::IndexType = vk::IndexType::UINT16;
+}
+
+impl IndexElement for u32 {
+    const INDEX_TYPE: vk::IndexType = vk::IndexType::UINT32;
+}
+
+/// Requires the `VK_EXT_index_type_uint8` extension and its `indexTypeUint8` feature to be enabled.
+#[cfg(feature = "index-type-uint8")]
+impl IndexElement for u8 {
+    const INDEX_TYPE: vk::IndexType = vk::IndexType::UINT8_EXT;
+}
+
+/// Bind `buffer` as index buffer, using the index type matching the buffer's element type.
+/// # Safety
+/// The buffer must have been created with `INDEX_BUFFER` usage.
+/// The command buffer must be in the recording state.
+pub unsafe fn bind_index_buffer<T: IndexElement>(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    buffer: &VulkanBuffer<T>,
+    offset_in_element_count: u64,
+) {
+    device.cmd_bind_index_buffer(
+        command_buffer,
+        buffer.buffer,
+        offset_in_element_count * std::mem::size_of::<T>() as u64,
+        T::INDEX_TYPE,
+    );
+}
+
+/// An index buffer with a dedicated memory allocation.
+pub struct IndexBuffer<T: IndexElement> {
+    pub buffer: VulkanBufferWithDedicatedAllocation<T>,
+}
+
+impl<T: IndexElement> IndexBuffer<T> {
+    /// Allocates a new index buffer. `usage` is extended by `INDEX_BUFFER`.
+    /// # Safety
+    /// Requires a valid device with sufficient memory.
+    pub unsafe fn allocate(
+        vk_engine: &VulkanEngine,
+        length: u64,
+        usage: vk::BufferUsageFlags,
+        sharing_mode: vk::SharingMode,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Self {
+        Self {
+            buffer: VulkanBufferWithDedicatedAllocation::allocate(
+                vk_engine,
+                length,
+                usage | vk::BufferUsageFlags::INDEX_BUFFER,
+                sharing_mode,
+                memory_property_flags,
+            ),
+        }
+    }
+
+    /// Get the length (in elements) of the buffer.
+    pub fn length(&self) -> u64 {
+        self.buffer.buffer.length
+    }
+
+    /// Bind `self` as index buffer.
+    /// # Safety
+    /// The command buffer must be in the recording state.
+    pub unsafe fn bind(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        offset_in_element_count: u64,
+    ) {
+        bind_index_buffer(
+            device,
+            command_buffer,
+            &self.buffer.buffer,
+            offset_in_element_count,
+        );
+    }
+
+    /// Frees the buffer and memory resources held by `self`.
+    /// # Safety
+    /// The memory and buffer must not be used anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        self.buffer.destroy(device);
+    }
+}
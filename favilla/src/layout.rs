@@ -0,0 +1,167 @@
+//! Layout-checked types for uniform and storage buffers.
+//!
+//! `Std140` and `Std430` are implemented for types whose Rust representation matches the
+//! corresponding GLSL layout rules. They can be derived for `#[repr(C)]` structs
+//! (requires the `derive` feature); the derived implementation verifies every field offset and
+//! the struct size at compile time, so a struct that doesn't match the GLSL layout fails to compile.
+//! Add `Padding<N>` fields where the GLSL layout requires padding.
+use cgmath::{Matrix2, Matrix4, Point2, Point3, Vector2, Vector3, Vector4};
+
+#[cfg(feature = "derive")]
+pub use favilla_derive::{Std140, Std430};
+
+/// Types whose Rust layout matches the GLSL `std140` layout.
+/// # Safety
+/// `ALIGNMENT` and `SIZE` must be the base alignment and size of the type under `std140` rules,
+/// and the Rust representation of the type must match that layout.
+pub unsafe trait Std140: Copy {
+    const ALIGNMENT: usize;
+    const SIZE: usize;
+}
+
+/// Types whose Rust layout matches the GLSL `std430` layout.
+/// # Safety
+/// `ALIGNMENT` and `SIZE` must be the base alignment and size of the type under `std430` rules,
+/// and the Rust representation of the type must match that layout.
+pub unsafe trait Std430: Copy {
+    const ALIGNMENT: usize;
+    const SIZE: usize;
+}
+
+/// Explicit padding bytes; they have no alignment requirements under either layout.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Padding<const N: usize>([u8; N]);
+
+impl<const N: usize> Default for Padding<N> {
+    fn default() -> Self {
+        Self([0; N])
+    }
+}
+
+/// Round `offset` up to the next multiple of `alignment`.
+pub const fn align_to(offset: usize, alignment: usize) -> usize {
+    offset.div_ceil(alignment) * alignment
+}
+
+/// The largest of the given alignments.
+pub const fn max_alignment(alignments: &[usize]) -> usize {
+    let mut max = 1;
+    let mut i = 0;
+    while i < alignments.len() {
+        if alignments[i] > max {
+            max = alignments[i];
+        }
+        i += 1;
+    }
+    max
+}
+
+/// The alignment of a struct under `std140` rules: the largest member alignment, rounded up to 16.
+pub const fn std140_struct_alignment(member_alignments: &[usize]) -> usize {
+    align_to(max_alignment(member_alignments), 16)
+}
+
+/// The distance between consecutive elements in a `std140` array of `T`.
+pub const fn std140_array_stride<T: Std140>() -> usize {
+    align_to(T::SIZE, align_to(T::ALIGNMENT, 16))
+}
+
+/// The distance between consecutive elements in a `std430` array of `T`.
+pub const fn std430_array_stride<T: Std430>() -> usize {
+    align_to(T::SIZE, T::ALIGNMENT)
+}
+
+/// Compile-time check that a slice of `T` has the layout of a `std140` array.
+pub(crate) struct AssertStd140Array<T>(std::marker::PhantomData<T>);
+
+impl<T: Std140> AssertStd140Array<T> {
+    pub(crate) const OK: () = assert!(
+        std140_array_stride::<T>() == std::mem::size_of::<T>(),
+        "the std140 array stride of the element type differs from its Rust size"
+    );
+}
+
+/// Compile-time check that a slice of `T` has the layout of a `std430` array.
+pub(crate) struct AssertStd430Array<T>(std::marker::PhantomData<T>);
+
+impl<T: Std430> AssertStd430Array<T> {
+    pub(crate) const OK: () = assert!(
+        std430_array_stride::<T>() == std::mem::size_of::<T>(),
+        "the std430 array stride of the element type differs from its Rust size"
+    );
+}
+
+macro_rules! impl_layout {
+    ($($ty:ty => ($alignment:expr, $size:expr)),* $(,)?) => {
+        $(
+            unsafe impl Std140 for $ty {
+                const ALIGNMENT: usize = $alignment;
+                const SIZE: usize = $size;
+            }
+
+            unsafe impl Std430 for $ty {
+                const ALIGNMENT: usize = $alignment;
+                const SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+impl_layout! {
+    f32 => (4, 4),
+    i32 => (4, 4),
+    u32 => (4, 4),
+    Vector2<f32> => (8, 8),
+    Vector2<i32> => (8, 8),
+    Vector2<u32> => (8, 8),
+    Point2<f32> => (8, 8),
+    Vector3<f32> => (16, 12),
+    Vector3<i32> => (16, 12),
+    Vector3<u32> => (16, 12),
+    Point3<f32> => (16, 12),
+    Vector4<f32> => (16, 16),
+    Vector4<i32> => (16, 16),
+    Vector4<u32> => (16, 16),
+    Matrix4<f32> => (16, 64),
+}
+
+// Under std140, the columns of a mat2 are padded to 16 bytes, which doesn't match `Matrix2<f32>`.
+unsafe impl Std430 for Matrix2<f32> {
+    const ALIGNMENT: usize = 8;
+    const SIZE: usize = 16;
+}
+
+unsafe impl<const N: usize> Std140 for Padding<N> {
+    const ALIGNMENT: usize = 1;
+    const SIZE: usize = N;
+}
+
+unsafe impl<const N: usize> Std430 for Padding<N> {
+    const ALIGNMENT: usize = 1;
+    const SIZE: usize = N;
+}
+
+unsafe impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGNMENT: usize = align_to(T::ALIGNMENT, 16);
+    const SIZE: usize = std140_array_stride::<T>() * N;
+}
+
+unsafe impl<T: Std430, const N: usize> Std430 for [T; N] {
+    const ALIGNMENT: usize = T::ALIGNMENT;
+    const SIZE: usize = std430_array_stride::<T>() * N;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn array_strides() {
+        assert_eq!(<[f32; 4] as Std140>::SIZE, 64);
+        assert_eq!(<[f32; 4] as Std430>::SIZE, 16);
+        assert_eq!(<[Vector3<f32>; 2] as Std430>::SIZE, 32);
+        assert_eq!(std140_array_stride::<Vector4<f32>>(), 16);
+        assert_eq!(std140_struct_alignment(&[4, 8]), 16);
+    }
+}
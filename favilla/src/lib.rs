@@ -1,18 +1,29 @@
 #![deny(rust_2018_idioms)]
 #![deny(clippy::all)]
 
+pub mod allocator;
 pub mod app;
 pub mod buffer;
 pub mod camera;
 pub mod cleanup;
 pub mod cleanup_queue;
 pub mod debug_utils;
+pub mod dynamic_buffer;
+pub mod format;
 pub mod frame_data;
+pub mod free_list_allocator;
+pub mod index_buffer;
 pub mod layer_names;
+pub mod layout;
 pub mod linear_allocator;
 pub mod memory;
 pub mod push_buffer;
 pub mod queue_families;
+pub mod render_target;
+pub mod sampler;
 pub mod swapchain;
 pub mod texture;
+pub mod texture_atlas;
+pub mod upload_batch;
+pub mod vertex;
 pub mod vk_engine;
@@ -1,38 +1,49 @@
+use crate::allocator::{supports_memory_type, Allocator};
+use crate::cleanup_queue::{CleanupQueue, CleanupTick};
 use ash::vk::{DeviceMemory, DeviceSize, MemoryRequirements};
 use ash::{vk, Device};
-use thiserror::Error;
+use std::collections::VecDeque;
+use std::ffi::c_void;
+use std::ptr::NonNull;
+
+pub use crate::allocator::{get_aligned_offset, SubAllocation, SubAllocationError};
 
 /// A simple linear allocator.
+/// Individual allocations can't be freed, but the allocator can be reset or rewound to a marker,
+/// which makes it usable as an arena for transient resources, e.g. per frame or per level.
+///
+/// Deferred rewinds turn the memory after the first deferred marker into a ring:
+/// memory they free is reused once the allocator reaches the end of its memory and wraps around.
 #[derive(Debug)]
 pub struct LinearAllocator {
     memory: DeviceMemory,
+    memory_type_index: u32,
+    allocate_flags: vk::MemoryAllocateFlags,
     size: u64,
-    free_section_start: u64,
+    /// Start of the ring; memory before it is only freed by `rewind` and `reset`.
+    ring_start: u64,
+    /// Positions only ever grow while the allocator wraps around, see `physical_offset`.
+    /// `tail` is the position of the oldest memory in the ring that is still in use.
+    tail: u64,
+    head: u64,
+    mapped_ptr: Option<NonNull<c_void>>,
+    pending_rewinds: VecDeque<PendingRewind>,
 }
 
-/// An allocation within a larger chunk of allocated memory.
-#[derive(Debug)]
-pub struct SubAllocation {
-    pub memory: DeviceMemory,
-    pub offset: u64,
-    pub size: u64,
-}
+/// A position in a `LinearAllocator`; see `LinearAllocator::mark`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LinearAllocatorMarker(u64);
 
-#[derive(Error, Debug)]
-pub enum SubAllocationError {
-    #[error("Out of memory")]
-    OutOfMemory,
+/// A range of memory that becomes free once the cleanup queue has passed `release_tick`.
+#[derive(Debug)]
+struct PendingRewind {
+    start: u64,
+    end: u64,
+    release_tick: CleanupTick,
 }
 
-pub fn get_aligned_offset(offset: u64, alignment: u64) -> u64 {
-    let misalignment = offset % alignment;
-    let padding = if misalignment == 0 {
-        0
-    } else {
-        alignment - misalignment
-    };
-    offset + padding
-}
+// See `SubAllocation`: the mapped pointer is only handed out, never dereferenced by the allocator.
+unsafe impl Send for LinearAllocator {}
 
 impl LinearAllocator {
     /// Create a new linear allocator with the given memory size and memory type index.
@@ -43,21 +54,57 @@ impl LinearAllocator {
         size: DeviceSize,
         memory_type_index: u32,
     ) -> Result<Self, vk::Result> {
-        let allocate_info = vk::MemoryAllocateInfo {
-            allocation_size: size,
+        Self::with_allocate_flags(
+            device,
+            size,
             memory_type_index,
-            ..Default::default()
-        };
+            vk::MemoryAllocateFlags::empty(),
+        )
+    }
+
+    /// Create a new linear allocator whose memory is allocated with the given flags.
+    /// Use `DEVICE_ADDRESS` to sub-allocate buffers with `SHADER_DEVICE_ADDRESS` usage.
+    /// # Safety
+    /// Requires a valid device with sufficient memory. The features required by the flags must be enabled.
+    pub unsafe fn with_allocate_flags(
+        device: &Device,
+        size: DeviceSize,
+        memory_type_index: u32,
+        allocate_flags: vk::MemoryAllocateFlags,
+    ) -> Result<Self, vk::Result> {
+        let mut allocate_flags_info = vk::MemoryAllocateFlagsInfo::builder().flags(allocate_flags);
+        let mut allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(size)
+            .memory_type_index(memory_type_index);
+        if !allocate_flags.is_empty() {
+            allocate_info = allocate_info.push_next(&mut allocate_flags_info);
+        }
 
         let memory = device.allocate_memory(&allocate_info, None)?;
 
         Ok(Self {
             memory,
+            memory_type_index,
+            allocate_flags,
             size,
-            free_section_start: 0,
+            ring_start: 0,
+            tail: 0,
+            head: 0,
+            mapped_ptr: None,
+            pending_rewinds: VecDeque::new(),
         })
     }
 
+    /// Persistently maps the allocator's memory. Allocations made afterwards carry a host pointer,
+    /// which allows creating staging buffers from them.
+    /// # Safety
+    /// The memory type has to be host visible. The memory must not be mapped already.
+    pub unsafe fn map(&mut self, device: &Device) -> Result<(), vk::Result> {
+        let ptr = device.map_memory(self.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())?;
+        self.mapped_ptr = NonNull::new(ptr);
+        Ok(())
+    }
+
     /// Try to get a free chunk of memory from the allocator.
     /// # Safety
     /// Memory must not have been freed.
@@ -65,21 +112,148 @@ impl LinearAllocator {
         &mut self,
         memory_req: MemoryRequirements,
     ) -> Result<SubAllocation, SubAllocationError> {
-        let offset = get_aligned_offset(self.free_section_start, memory_req.alignment);
-        let new_start = offset + memory_req.size;
+        if !supports_memory_type(&memory_req, self.memory_type_index) {
+            return Err(SubAllocationError::IncompatibleMemoryType(
+                self.memory_type_index,
+            ));
+        }
 
-        if new_start <= self.size {
-            self.free_section_start = new_start;
+        let head_offset = self.physical_offset(self.head);
+        let mut offset = get_aligned_offset(head_offset, memory_req.alignment);
+        let mut position = self.head + (offset - head_offset);
+        if offset + memory_req.size > self.size {
+            // Skip the rest of the memory and wrap around to the start of the ring.
+            offset = get_aligned_offset(self.ring_start, memory_req.alignment);
+            position = self.head + (self.size - head_offset) + (offset - self.ring_start);
+        }
+        let new_head = position + memory_req.size;
+
+        if offset + memory_req.size <= self.size && new_head - self.tail <= self.ring_size() {
+            self.head = new_head;
             Ok(SubAllocation {
                 memory: self.memory,
                 offset,
                 size: memory_req.size,
+                mapped_ptr: self
+                    .mapped_ptr
+                    .map(|ptr| NonNull::new_unchecked(ptr.as_ptr().add(offset as usize))),
             })
         } else {
             Err(SubAllocationError::OutOfMemory)
         }
     }
 
+    /// Get a marker of the allocator's current position. Rewinding to it frees all allocations made afterwards.
+    pub fn mark(&self) -> LinearAllocatorMarker {
+        LinearAllocatorMarker(self.head)
+    }
+
+    fn ring_size(&self) -> u64 {
+        self.size - self.ring_start
+    }
+
+    /// Maps a position to its offset in memory. Positions in the ring keep growing with every lap.
+    fn physical_offset(&self, position: u64) -> u64 {
+        if position < self.ring_start || self.ring_size() == 0 {
+            position
+        } else {
+            self.ring_start + (position - self.ring_start) % self.ring_size()
+        }
+    }
+
+    /// Frees all allocations made after `marker` was taken.
+    /// Deferred rewinds of the freed memory are dropped.
+    /// # Safety
+    /// The freed allocations must not be used anymore. The marker must have been taken from this allocator
+    /// and must not lie beyond its current position, i.e. the allocator must not have been rewound past it.
+    pub unsafe fn rewind(&mut self, marker: LinearAllocatorMarker) {
+        assert!(
+            marker.0 <= self.head,
+            "The marker lies beyond the allocator's current position"
+        );
+        self.head = marker.0;
+        for pending in &mut self.pending_rewinds {
+            pending.end = pending.end.min(marker.0);
+        }
+        self.pending_rewinds
+            .retain(|pending| pending.start < pending.end);
+        if marker.0 < self.tail {
+            // Everything after the marker is free, so the ring is empty.
+            self.tail = marker.0;
+            self.ring_start = self.ring_start.min(marker.0);
+        }
+    }
+
+    /// Frees all allocations.
+    /// # Safety
+    /// The allocations must not be used anymore.
+    pub unsafe fn reset(&mut self) {
+        self.rewind(LinearAllocatorMarker(0));
+    }
+
+    /// Like `rewind`, but the allocations made after `marker` was taken are only freed once the frames
+    /// currently in flight have finished, as tracked by `cleanup_queue`; see `release_deferred`.
+    /// New allocations are placed after the deferred allocations and wrap around to freed memory,
+    /// so a per-frame loop of `release_deferred`, `mark`, allocating and `rewind_deferred` keeps reusing the same memory.
+    /// Deferred rewinds can't be nested: the marker must not lie before memory that is already being rewound.
+    /// # Safety
+    /// The freed allocations must not be used in frames recorded from now on. See `rewind` for the marker.
+    pub unsafe fn rewind_deferred(
+        &mut self,
+        marker: LinearAllocatorMarker,
+        cleanup_queue: &CleanupQueue,
+    ) {
+        assert!(
+            marker.0 <= self.head,
+            "The marker lies beyond the allocator's current position"
+        );
+        let rewound_until = self
+            .pending_rewinds
+            .back()
+            .map_or(self.tail, |pending| pending.end);
+        assert!(
+            marker.0 >= rewound_until,
+            "The marker lies before memory that is already being rewound"
+        );
+        if self.pending_rewinds.is_empty() && self.head <= self.size {
+            // The allocator hasn't wrapped around, so the ring can start at the marker;
+            // allocations before it are kept until `rewind` or `reset`.
+            self.ring_start = marker.0;
+            self.tail = marker.0;
+        }
+        if marker.0 < self.head {
+            self.pending_rewinds.push_back(PendingRewind {
+                start: marker.0,
+                end: self.head,
+                release_tick: cleanup_queue.release_tick(),
+            });
+        }
+    }
+
+    /// Like `reset`, but deferred; see `rewind_deferred`.
+    /// # Safety
+    /// The allocations must not be used in frames recorded from now on.
+    pub unsafe fn reset_deferred(&mut self, cleanup_queue: &CleanupQueue) {
+        self.rewind_deferred(LinearAllocatorMarker(0), cleanup_queue);
+    }
+
+    /// Applies deferred rewinds whose frames have finished, e.g. once per frame after ticking `cleanup_queue`.
+    /// Memory is reclaimed in the order it was allocated in: allocations in the ring that weren't rewound
+    /// keep the memory after them from being reused until they are freed by `rewind` or `reset`.
+    pub fn release_deferred(&mut self, cleanup_queue: &CleanupQueue) {
+        self.release_pending(|tick| cleanup_queue.has_passed(tick));
+    }
+
+    fn release_pending(&mut self, has_passed: impl Fn(CleanupTick) -> bool) {
+        while let Some(pending) = self.pending_rewinds.front() {
+            if pending.start != self.tail || !has_passed(pending.release_tick) {
+                break;
+            }
+            self.tail = pending.end;
+            self.pending_rewinds.pop_front();
+        }
+    }
+
     /// Frees the allocator's memory.
     /// # Safety
     /// Memory must not be used anymore.
@@ -87,3 +261,125 @@ impl LinearAllocator {
         device.free_memory(self.memory, None);
     }
 }
+
+impl Allocator for LinearAllocator {
+    fn memory_type_index(&self) -> u32 {
+        self.memory_type_index
+    }
+
+    fn allocate_flags(&self) -> vk::MemoryAllocateFlags {
+        self.allocate_flags
+    }
+
+    fn is_mapped(&self) -> bool {
+        self.mapped_ptr.is_some()
+    }
+
+    fn owns(&self, allocation: &SubAllocation) -> bool {
+        allocation.memory == self.memory
+    }
+
+    unsafe fn allocate(
+        &mut self,
+        _device: &Device,
+        memory_req: MemoryRequirements,
+    ) -> Result<SubAllocation, SubAllocationError> {
+        LinearAllocator::allocate(self, memory_req)
+    }
+
+    unsafe fn free(&mut self, _allocation: SubAllocation) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocator(size: u64) -> LinearAllocator {
+        LinearAllocator {
+            memory: DeviceMemory::null(),
+            memory_type_index: 0,
+            allocate_flags: vk::MemoryAllocateFlags::empty(),
+            size,
+            ring_start: 0,
+            tail: 0,
+            head: 0,
+            mapped_ptr: None,
+            pending_rewinds: VecDeque::new(),
+        }
+    }
+
+    fn requirements(size: u64) -> MemoryRequirements {
+        MemoryRequirements {
+            size,
+            alignment: 16,
+            memory_type_bits: 1,
+        }
+    }
+
+    #[test]
+    fn markers_and_deferred_rewinds() {
+        unsafe {
+            let mut allocator = allocator(256);
+            allocator.allocate(requirements(10)).unwrap();
+            let level = allocator.mark();
+            assert_eq!(allocator.allocate(requirements(32)).unwrap().offset, 16);
+            allocator.rewind(level);
+            assert_eq!(allocator.allocate(requirements(32)).unwrap().offset, 16);
+
+            let queue = CleanupQueue::new(2);
+            let frame = allocator.mark();
+            allocator.allocate(requirements(64)).unwrap();
+            allocator.rewind_deferred(frame, &queue);
+            let next_frame = allocator.mark();
+            assert_eq!(next_frame.0, 112);
+            allocator.allocate(requirements(16)).unwrap();
+            allocator.rewind_deferred(next_frame, &queue);
+
+            // Nothing is freed until the frames have finished.
+            let first_tick = queue.release_tick();
+            allocator.release_pending(|_| false);
+            assert_eq!(allocator.allocate(requirements(128)).unwrap().offset, 128);
+            assert!(allocator.allocate(requirements(16)).is_err());
+
+            // Afterwards, the allocator wraps around to the freed memory, but not into the allocations before the ring.
+            allocator.release_pending(|tick| tick <= first_tick);
+            assert_eq!(allocator.allocate(requirements(80)).unwrap().offset, 48);
+            assert!(allocator.allocate(requirements(16)).is_err());
+
+            allocator.reset();
+            assert_eq!(allocator.allocate(requirements(256)).unwrap().offset, 0);
+            assert!(allocator.allocate(requirements(1)).is_err());
+        }
+    }
+
+    #[test]
+    fn per_frame_arena_reuses_memory() {
+        unsafe {
+            let num_frames = 2;
+            let mut allocator = allocator(1024);
+            let level = allocator.allocate(requirements(100)).unwrap();
+            let mut queue = CleanupQueue::new(num_frames);
+            let mut wrapped = false;
+            let mut previous_offset = 0;
+
+            for frame in 0..8 * num_frames {
+                queue.advance();
+                allocator.release_deferred(&queue);
+                let marker = allocator.mark();
+                // Varying sizes so the frames don't divide the ring evenly.
+                let allocation = allocator
+                    .allocate(requirements(200 + 10 * (frame as u64 % 3)))
+                    .unwrap();
+                assert!(allocation.offset >= level.size);
+                assert!(allocation.offset + allocation.size <= 1024);
+                wrapped |= allocation.offset < previous_offset;
+                previous_offset = allocation.offset;
+                allocator.rewind_deferred(marker, &queue);
+
+                // At most the frames in flight and the current one are alive.
+                assert!(allocator.head - allocator.tail <= (num_frames as u64 + 1) * 240);
+            }
+            assert!(wrapped);
+        }
+    }
+}
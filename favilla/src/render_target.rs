@@ -0,0 +1,260 @@
+use crate::texture::layout_state::LayoutTransitions;
+use crate::texture::{ImageAccess, Texture, TextureWithDedicatedAllocation};
+use crate::vk_engine::VulkanEngine;
+use ash::vk::ImageLayout;
+use ash::{vk, Device};
+
+/// A texture with its own memory allocation and a view of it.
+pub struct RenderTargetAttachment {
+    pub texture: Texture,
+    pub view: vk::ImageView,
+    pub memory: vk::DeviceMemory,
+}
+
+impl RenderTargetAttachment {
+    unsafe fn allocate(vk_engine: &VulkanEngine, texture: Texture) -> Result<Self, vk::Result> {
+        let mut allocated = TextureWithDedicatedAllocation::allocate(
+            vk_engine,
+            texture,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        let texture = &allocated.texture;
+        let view = texture.create_view(
+            &vk_engine.device,
+            vk::ImageViewType::TYPE_2D,
+            texture.full_range(),
+        );
+        match view {
+            Ok(view) => Ok(Self {
+                texture: allocated.texture,
+                view,
+                memory: allocated.memory,
+            }),
+            Err(err) => {
+                allocated.destroy(&vk_engine.device);
+                Err(err)
+            }
+        }
+    }
+
+    /// Frees the view, image and memory held by `self`.
+    /// # Safety
+    /// The attachment must not be used anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        device.destroy_image_view(self.view, None);
+        self.texture.destroy(device);
+        device.free_memory(self.memory, None);
+    }
+}
+
+/// An offscreen render target: a colour attachment that is sampled after rendering,
+/// with an optional depth attachment and an optional framebuffer for a given render pass.
+///
+/// `begin` transitions the colour attachment to `COLOR_ATTACHMENT_OPTIMAL` and `end` transitions it
+/// for the next pass, e.g. to `SHADER_READ_ONLY_OPTIMAL`. When using a render pass, its colour attachment
+/// should have `COLOR_ATTACHMENT_OPTIMAL` as initial and final layout, and its depth attachment
+/// `DEPTH_STENCIL_ATTACHMENT_OPTIMAL`. With dynamic rendering, use `color_attachment_info` and
+/// `depth_attachment_info` instead of the framebuffer.
+pub struct RenderTarget {
+    pub extent: vk::Extent2D,
+    pub color: RenderTargetAttachment,
+    pub depth: Option<RenderTargetAttachment>,
+    pub render_pass: Option<vk::RenderPass>,
+    pub framebuffer: Option<vk::Framebuffer>,
+}
+
+impl RenderTarget {
+    /// Creates a new render target with the given extent. The colour attachment can be sampled.
+    /// If `render_pass` is given, a framebuffer with the colour attachment (and the depth attachment,
+    /// if present) is created for it.
+    /// # Safety
+    /// Device must be valid. The formats must support being used as the respective attachment.
+    /// Requires Vulkan 1.1, as the attachments may get dedicated allocations (see `TextureWithDedicatedAllocation`).
+    pub unsafe fn new(
+        vk_engine: &VulkanEngine,
+        extent: vk::Extent2D,
+        color_format: vk::Format,
+        depth_format: Option<vk::Format>,
+        render_pass: Option<vk::RenderPass>,
+    ) -> Result<Self, vk::Result> {
+        let color = RenderTargetAttachment::allocate(
+            vk_engine,
+            Texture::new(
+                vk_engine,
+                color_format,
+                vk::ImageType::TYPE_2D,
+                vk::Extent3D {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                },
+                1,
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            )?,
+        )?;
+
+        let mut render_target = Self {
+            extent,
+            color,
+            depth: None,
+            render_pass,
+            framebuffer: None,
+        };
+
+        let result = render_target.create_depth_and_framebuffer(vk_engine, depth_format);
+        if let Err(err) = result {
+            render_target.destroy(&vk_engine.device);
+            return Err(err);
+        }
+        Ok(render_target)
+    }
+
+    unsafe fn create_depth_and_framebuffer(
+        &mut self,
+        vk_engine: &VulkanEngine,
+        depth_format: Option<vk::Format>,
+    ) -> Result<(), vk::Result> {
+        if let Some(depth_format) = depth_format {
+            self.depth = Some(RenderTargetAttachment::allocate(
+                vk_engine,
+                Texture::new_depth(vk_engine, depth_format, self.extent, false)?,
+            )?);
+        }
+
+        if let Some(render_pass) = self.render_pass {
+            let mut attachments = vec![self.color.view];
+            attachments.extend(self.depth.as_ref().map(|depth| depth.view));
+            self.framebuffer = Some(
+                vk_engine.device.create_framebuffer(
+                    &vk::FramebufferCreateInfo::builder()
+                        .render_pass(render_pass)
+                        .attachments(&attachments)
+                        .width(self.extent.width)
+                        .height(self.extent.height)
+                        .layers(1),
+                    None,
+                )?,
+            );
+        }
+        Ok(())
+    }
+
+    /// Recreates all attachments and the framebuffer with the new extent.
+    /// The content of the render target is discarded.
+    /// # Safety
+    /// The render target must not be in use.
+    pub unsafe fn resize(
+        &mut self,
+        vk_engine: &VulkanEngine,
+        extent: vk::Extent2D,
+    ) -> Result<(), vk::Result> {
+        let color_format = self.color.texture.format;
+        let depth_format = self.depth.as_ref().map(|depth| depth.texture.format);
+        let resized = Self::new(
+            vk_engine,
+            extent,
+            color_format,
+            depth_format,
+            self.render_pass,
+        )?;
+        let mut old = std::mem::replace(self, resized);
+        old.destroy(&vk_engine.device);
+        Ok(())
+    }
+
+    /// The area covered by the render target, e.g. for the render area and scissor.
+    pub fn render_area(&self) -> vk::Rect2D {
+        vk::Rect2D {
+            offset: vk::Offset2D::default(),
+            extent: self.extent,
+        }
+    }
+
+    /// Get the colour attachment description for dynamic rendering.
+    pub fn color_attachment_info(
+        &self,
+        load_op: vk::AttachmentLoadOp,
+        clear_value: vk::ClearColorValue,
+    ) -> vk::RenderingAttachmentInfo {
+        vk::RenderingAttachmentInfo::builder()
+            .image_view(self.color.view)
+            .image_layout(ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(load_op)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .clear_value(vk::ClearValue { color: clear_value })
+            .build()
+    }
+
+    /// Get the depth attachment description for dynamic rendering, if the render target has one.
+    /// The depth attachment is cleared on every use.
+    pub fn depth_attachment_info(
+        &self,
+        clear_value: vk::ClearDepthStencilValue,
+    ) -> Option<vk::RenderingAttachmentInfo> {
+        self.depth.as_ref().map(|depth| {
+            vk::RenderingAttachmentInfo::builder()
+                .image_view(depth.view)
+                .image_layout(ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .clear_value(vk::ClearValue {
+                    depth_stencil: clear_value,
+                })
+                .build()
+        })
+    }
+
+    /// Records the transitions of the attachments into attachment layouts, waiting for the previous
+    /// access of the colour attachment as tracked by its layout state. The depth attachment's content is discarded.
+    /// Both transitions are recorded with a single barrier.
+    /// # Safety
+    /// `command_buffer` must be in the recording state and outside of a render pass.
+    pub unsafe fn begin(&mut self, device: &Device, command_buffer: vk::CommandBuffer) {
+        let mut transitions = LayoutTransitions::new();
+        let range = self.color.texture.full_range();
+        transitions.add(
+            &mut self.color.texture,
+            range,
+            ImageAccess::COLOR_ATTACHMENT,
+        );
+        if let Some(depth) = &mut self.depth {
+            let range = depth.texture.full_range();
+            depth.texture.layout_state.discard(range);
+            transitions.add(
+                &mut depth.texture,
+                range,
+                ImageAccess::DEPTH_STENCIL_ATTACHMENT,
+            );
+        }
+        transitions.record(device, command_buffer);
+    }
+
+    /// Records the transition of the colour attachment for the next pass, e.g.
+    /// `ImageAccess::FRAGMENT_SHADER_READ` to sample it while compositing.
+    /// # Safety
+    /// `command_buffer` must be in the recording state and outside of a render pass.
+    /// `begin` must have been recorded before.
+    pub unsafe fn end(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        dst: ImageAccess,
+    ) {
+        self.color
+            .texture
+            .transition_to(device, command_buffer, dst);
+    }
+
+    /// Frees all resources held by `self`. The render pass is not owned by the render target.
+    /// # Safety
+    /// The render target must not be used anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        if let Some(framebuffer) = self.framebuffer.take() {
+            device.destroy_framebuffer(framebuffer, None);
+        }
+        if let Some(depth) = &mut self.depth {
+            depth.destroy(device);
+        }
+        self.color.destroy(device);
+    }
+}
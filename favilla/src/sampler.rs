@@ -0,0 +1,174 @@
+use crate::vk_engine::VulkanEngine;
+use ash::{vk, Device, Instance};
+use std::collections::HashMap;
+
+/// A hashable description of a sampler.
+/// Samplers use the full mip chain of the sampled texture.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SamplerDescription {
+    pub mag_filter: vk::Filter,
+    pub min_filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode_u: vk::SamplerAddressMode,
+    pub address_mode_v: vk::SamplerAddressMode,
+    pub address_mode_w: vk::SamplerAddressMode,
+    /// Maximum anisotropy, or `None` to disable anisotropic filtering.
+    /// Clamped to 1 and the device limit; requires the `samplerAnisotropy` device feature.
+    pub max_anisotropy: Option<u32>,
+    pub border_color: vk::BorderColor,
+    /// Comparison against a reference value, e.g. for shadow maps; `None` to disable comparison.
+    pub compare_op: Option<vk::CompareOp>,
+}
+
+impl SamplerDescription {
+    /// A sampler with linear filtering and the given address mode on all axes.
+    pub fn linear(address_mode: vk::SamplerAddressMode) -> Self {
+        Self {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            max_anisotropy: None,
+            border_color: vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+            compare_op: None,
+        }
+    }
+
+    /// A sampler with nearest filtering and the given address mode on all axes.
+    pub fn nearest(address_mode: vk::SamplerAddressMode) -> Self {
+        Self {
+            mag_filter: vk::Filter::NEAREST,
+            min_filter: vk::Filter::NEAREST,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            ..Self::linear(address_mode)
+        }
+    }
+}
+
+impl Default for SamplerDescription {
+    fn default() -> Self {
+        Self::linear(vk::SamplerAddressMode::REPEAT)
+    }
+}
+
+/// Creates samplers on demand and hands out the same `vk::Sampler` for equal descriptions.
+pub struct SamplerCache {
+    samplers: HashMap<SamplerDescription, vk::Sampler>,
+    max_sampler_anisotropy: f32,
+}
+
+impl SamplerCache {
+    /// Create a new, empty sampler cache for the device of `vk_engine`.
+    /// # Safety
+    /// Instance and physical device must be valid.
+    pub unsafe fn new(instance: &Instance, vk_engine: &VulkanEngine) -> Self {
+        let properties = instance.get_physical_device_properties(vk_engine.physical_device);
+        Self {
+            samplers: HashMap::new(),
+            max_sampler_anisotropy: properties.limits.max_sampler_anisotropy,
+        }
+    }
+
+    /// Get the description of the sampler actually created for `description`, with the anisotropy clamped.
+    /// Descriptions with the same effective description share a sampler.
+    pub fn effective_description(&self, description: SamplerDescription) -> SamplerDescription {
+        let limit = (self.max_sampler_anisotropy as u32).max(1);
+        SamplerDescription {
+            max_anisotropy: description
+                .max_anisotropy
+                .map(|max_anisotropy| max_anisotropy.clamp(1, limit)),
+            ..description
+        }
+    }
+
+    fn cached(&self, description: &SamplerDescription) -> Option<vk::Sampler> {
+        self.samplers.get(description).copied()
+    }
+
+    /// Get the sampler for the given description, creating it if necessary.
+    /// # Safety
+    /// Device must be valid.
+    pub unsafe fn get(
+        &mut self,
+        device: &Device,
+        description: SamplerDescription,
+    ) -> Result<vk::Sampler, vk::Result> {
+        let description = self.effective_description(description);
+        if let Some(sampler) = self.cached(&description) {
+            return Ok(sampler);
+        }
+
+        let max_anisotropy = description
+            .max_anisotropy
+            .map(|max_anisotropy| max_anisotropy as f32);
+        let sampler = device.create_sampler(
+            &vk::SamplerCreateInfo::builder()
+                .mag_filter(description.mag_filter)
+                .min_filter(description.min_filter)
+                .mipmap_mode(description.mipmap_mode)
+                .address_mode_u(description.address_mode_u)
+                .address_mode_v(description.address_mode_v)
+                .address_mode_w(description.address_mode_w)
+                .anisotropy_enable(max_anisotropy.is_some())
+                .max_anisotropy(max_anisotropy.unwrap_or(1.0))
+                .compare_enable(description.compare_op.is_some())
+                .compare_op(description.compare_op.unwrap_or(vk::CompareOp::ALWAYS))
+                .min_lod(0.0)
+                .max_lod(vk::LOD_CLAMP_NONE)
+                .border_color(description.border_color),
+            None,
+        )?;
+        self.samplers.insert(description, sampler);
+        Ok(sampler)
+    }
+
+    /// Destroys all samplers held by `self`.
+    /// # Safety
+    /// The samplers must not be used anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        for (_, sampler) in self.samplers.drain() {
+            device.destroy_sampler(sampler, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ash::vk::Handle;
+
+    #[test]
+    fn clamped_anisotropy_is_deduplicated() {
+        let mut cache = SamplerCache {
+            samplers: HashMap::new(),
+            max_sampler_anisotropy: 16.0,
+        };
+        let anisotropic = |max_anisotropy| SamplerDescription {
+            max_anisotropy: Some(max_anisotropy),
+            ..SamplerDescription::default()
+        };
+        assert_eq!(
+            cache.effective_description(anisotropic(0)).max_anisotropy,
+            Some(1)
+        );
+        assert_eq!(
+            cache.effective_description(anisotropic(64)).max_anisotropy,
+            Some(16)
+        );
+        assert_eq!(
+            cache.effective_description(SamplerDescription::default()),
+            SamplerDescription::default()
+        );
+
+        let sampler = vk::Sampler::from_raw(1);
+        cache
+            .samplers
+            .insert(cache.effective_description(anisotropic(16)), sampler);
+        let cached = |description| cache.cached(&cache.effective_description(description));
+        assert_eq!(cached(anisotropic(64)), Some(sampler));
+        assert_eq!(cached(anisotropic(8)), None);
+        assert_eq!(cached(SamplerDescription::default()), None);
+    }
+}
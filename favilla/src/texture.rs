@@ -1,29 +1,462 @@
 use ash::vk;
 
-use crate::buffer::StagingBuffer;
+use crate::allocator::{Allocator, SubAllocation, SubAllocationError};
+use crate::buffer::{StagingBuffer, StagingBufferWithDedicatedAllocation};
+use crate::format::{aspect_mask, format_info, FormatKind};
+use crate::memory::find_memory_type_index;
 
 use crate::vk_engine::VulkanEngine;
 use ash::vk::{ImageLayout, ImageMemoryBarrier};
-use ash::Device;
+use ash::{Device, Instance};
+use thiserror::Error;
+use tracing::{event, Level};
 
-/// A Texture struct. Holds a vk::Image and information about its format, extent and number of layers.
+pub mod array;
+#[cfg(any(feature = "ktx2", feature = "dds"))]
+pub mod container;
+pub mod convert;
+pub mod cube;
+pub mod layout_state;
+#[cfg(feature = "image")]
+pub mod load;
+pub mod mipmap;
+
+use layout_state::{LayoutState, LayoutTransitions};
+use mipmap::{mip_extent, mip_level_count, u8_channel_count, MipChain};
+
+/// A Texture struct. Holds a vk::Image and information about its format, type, extent, number of layers
+/// and number of mip levels, as well as the tracked layout of every mip level and layer.
 pub struct Texture {
     pub format: vk::Format,
     pub image: vk::Image,
+    pub image_type: vk::ImageType,
+    /// The flags the image was created with, e.g. `CUBE_COMPATIBLE` for cube maps.
+    pub flags: vk::ImageCreateFlags,
     pub extent: vk::Extent3D,
     pub num_array_layers: u32,
+    pub mip_levels: u32,
+    pub layout_state: LayoutState,
+}
+
+/// How an image is used, e.g. after an upload: the pipeline stages and accesses that have to wait for
+/// previous accesses, and the layout the image is transitioned to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ImageAccess {
+    pub stage_mask: vk::PipelineStageFlags,
+    pub access_mask: vk::AccessFlags,
+    pub layout: vk::ImageLayout,
+}
+
+impl ImageAccess {
+    /// The state of a newly created image, which hasn't been accessed yet.
+    pub const UNDEFINED: Self = Self {
+        stage_mask: vk::PipelineStageFlags::TOP_OF_PIPE,
+        access_mask: vk::AccessFlags::empty(),
+        layout: ImageLayout::UNDEFINED,
+    };
+
+    /// Sampled in a fragment shader.
+    pub const FRAGMENT_SHADER_READ: Self = Self {
+        stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+        access_mask: vk::AccessFlags::SHADER_READ,
+        layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    };
+
+    /// Sampled in a vertex shader.
+    pub const VERTEX_SHADER_READ: Self = Self {
+        stage_mask: vk::PipelineStageFlags::VERTEX_SHADER,
+        ..Self::FRAGMENT_SHADER_READ
+    };
+
+    /// Sampled in a compute shader.
+    pub const COMPUTE_SHADER_READ: Self = Self {
+        stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+        ..Self::FRAGMENT_SHADER_READ
+    };
+
+    /// Read and written as a storage image in a compute shader.
+    pub const COMPUTE_STORAGE: Self = Self {
+        stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+        access_mask: vk::AccessFlags::from_raw(
+            vk::AccessFlags::SHADER_READ.as_raw() | vk::AccessFlags::SHADER_WRITE.as_raw(),
+        ),
+        layout: ImageLayout::GENERAL,
+    };
+
+    /// Used as the destination of a transfer, e.g. a copy or blit.
+    pub const TRANSFER_DST: Self = Self {
+        stage_mask: vk::PipelineStageFlags::TRANSFER,
+        access_mask: vk::AccessFlags::TRANSFER_WRITE,
+        layout: ImageLayout::TRANSFER_DST_OPTIMAL,
+    };
+
+    /// Used as the source of a transfer, e.g. a copy or blit.
+    pub const TRANSFER_SRC: Self = Self {
+        stage_mask: vk::PipelineStageFlags::TRANSFER,
+        access_mask: vk::AccessFlags::TRANSFER_READ,
+        layout: ImageLayout::TRANSFER_SRC_OPTIMAL,
+    };
+
+    /// Read and written as a colour attachment.
+    pub const COLOR_ATTACHMENT: Self = Self {
+        stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        access_mask: vk::AccessFlags::from_raw(
+            vk::AccessFlags::COLOR_ATTACHMENT_READ.as_raw()
+                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE.as_raw(),
+        ),
+        layout: ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    };
+
+    /// Read and written as a depth-stencil attachment.
+    pub const DEPTH_STENCIL_ATTACHMENT: Self = Self {
+        stage_mask: vk::PipelineStageFlags::from_raw(
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS.as_raw()
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS.as_raw(),
+        ),
+        access_mask: vk::AccessFlags::from_raw(
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ.as_raw()
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw(),
+        ),
+        layout: ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    };
+
+    /// A depth-stencil texture sampled in a fragment shader, e.g. a shadow map.
+    pub const FRAGMENT_SHADER_DEPTH_READ: Self = Self {
+        stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+        access_mask: vk::AccessFlags::SHADER_READ,
+        layout: ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+    };
+
+    /// Get the image usage required for images in this access's layout.
+    pub fn required_usage(&self) -> vk::ImageUsageFlags {
+        match self.layout {
+            ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            | ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+            | ImageLayout::DEPTH_READ_ONLY_OPTIMAL
+            | ImageLayout::STENCIL_READ_ONLY_OPTIMAL => vk::ImageUsageFlags::SAMPLED,
+            ImageLayout::GENERAL => vk::ImageUsageFlags::STORAGE,
+            ImageLayout::TRANSFER_SRC_OPTIMAL => vk::ImageUsageFlags::TRANSFER_SRC,
+            ImageLayout::TRANSFER_DST_OPTIMAL => vk::ImageUsageFlags::TRANSFER_DST,
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+            | ImageLayout::DEPTH_ATTACHMENT_OPTIMAL
+            | ImageLayout::STENCIL_ATTACHMENT_OPTIMAL => {
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT
+            }
+            _ => vk::ImageUsageFlags::empty(),
+        }
+    }
+
+    /// Get the format features required for images in this access's layout, e.g. to pick a format
+    /// with `format::choose_supported_format`. Transfers are not covered, as their format features
+    /// are only reported by Vulkan 1.1 devices.
+    pub fn required_format_features(&self) -> vk::FormatFeatureFlags {
+        match self.layout {
+            ImageLayout::SHADER_READ_ONLY_OPTIMAL
+            | ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+            | ImageLayout::DEPTH_READ_ONLY_OPTIMAL
+            | ImageLayout::STENCIL_READ_ONLY_OPTIMAL => vk::FormatFeatureFlags::SAMPLED_IMAGE,
+            ImageLayout::GENERAL => vk::FormatFeatureFlags::STORAGE_IMAGE,
+            ImageLayout::COLOR_ATTACHMENT_OPTIMAL => vk::FormatFeatureFlags::COLOR_ATTACHMENT,
+            ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+            | ImageLayout::DEPTH_ATTACHMENT_OPTIMAL
+            | ImageLayout::STENCIL_ATTACHMENT_OPTIMAL => {
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+            }
+            _ => vk::FormatFeatureFlags::empty(),
+        }
+    }
+}
+
+/// A range of mip levels and array layers of a texture.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TextureRange {
+    pub base_mip_level: u32,
+    pub level_count: u32,
+    pub base_array_layer: u32,
+    pub layer_count: u32,
+}
+
+/// A region of a texture to update, together with the position of its data in the source buffer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TextureRegion {
+    /// Offset in bytes of the region's data, relative to the start of the uploaded data.
+    pub data_offset: vk::DeviceSize,
+    /// Length in texels of a row of the source data, or 0 if the rows are tightly packed.
+    /// Allows updating a sub-rectangle from a larger image in memory.
+    pub row_length: u32,
+    /// The aspect to copy to, or empty for the texture's `copy_aspect_mask`.
+    /// Set to `STENCIL` to update the stencil aspect of a depth-stencil texture.
+    pub aspect_mask: vk::ImageAspectFlags,
+    pub mip_level: u32,
+    pub base_array_layer: u32,
+    pub layer_count: u32,
+    pub offset: vk::Offset3D,
+    pub extent: vk::Extent3D,
+}
+
+impl TextureRegion {
+    /// The full extent of a single mip level of `texture`, covering all array layers.
+    pub fn mip_level(texture: &Texture, mip_level: u32) -> Self {
+        Self {
+            data_offset: 0,
+            row_length: 0,
+            aspect_mask: vk::ImageAspectFlags::empty(),
+            mip_level,
+            base_array_layer: 0,
+            layer_count: texture.num_array_layers,
+            offset: vk::Offset3D::default(),
+            extent: mip_extent(texture.extent, mip_level),
+        }
+    }
+
+    /// The full extent of mip level 0 of a single array layer of `texture`.
+    pub fn layer(texture: &Texture, array_layer: u32) -> Self {
+        Self {
+            base_array_layer: array_layer,
+            layer_count: 1,
+            ..Self::mip_level(texture, 0)
+        }
+    }
+
+    /// A range of depth slices of the given mip level of a 3D texture.
+    pub fn slices(texture: &Texture, mip_level: u32, base_slice: u32, slice_count: u32) -> Self {
+        let level = Self::mip_level(texture, mip_level);
+        Self {
+            offset: vk::Offset3D {
+                z: base_slice as i32,
+                ..level.offset
+            },
+            extent: vk::Extent3D {
+                depth: slice_count,
+                ..level.extent
+            },
+            ..level
+        }
+    }
+
+    /// A rectangle in mip level 0 of a single array layer of a 2D texture.
+    pub fn rect(array_layer: u32, offset: vk::Offset2D, extent: vk::Extent2D) -> Self {
+        Self {
+            data_offset: 0,
+            row_length: 0,
+            aspect_mask: vk::ImageAspectFlags::empty(),
+            mip_level: 0,
+            base_array_layer: array_layer,
+            layer_count: 1,
+            offset: vk::Offset3D {
+                x: offset.x,
+                y: offset.y,
+                z: 0,
+            },
+            extent: vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TextureCopyError {
+    #[error("The region exceeds the bounds of the texture")]
+    RegionOutOfBounds,
+    #[error("The region's data exceeds the bounds of the source data")]
+    DataOutOfBounds,
+    #[error("The region's aspect is not a single aspect of the texture")]
+    InvalidAspect,
+    #[error("The region's data offset is not a multiple of the format's texel block size")]
+    MisalignedDataOffset,
+    #[error("The region overlaps a region already added for the same texture")]
+    OverlappingRegions,
+}
+
+/// A texture created by one of favilla's loaders, together with its memory and a view of it.
+pub struct LoadedTexture {
+    pub texture: Texture,
+    pub view: vk::ImageView,
+    pub allocation: SubAllocation,
+}
+
+impl LoadedTexture {
+    /// Frees the image view and image held by `self`.
+    /// The memory is owned by the allocator it was allocated from.
+    /// # Safety
+    /// The texture must not be used anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        device.destroy_image_view(self.view, None);
+        self.texture.destroy(device);
+    }
+
+    /// Binds memory from `allocator` to `texture`, uploads its data with `upload` and creates a view
+    /// of the whole texture with the given view type. Destroys the texture and frees its memory on failure.
+    /// # Safety
+    /// The upload must have finished when `upload` returns.
+    pub(crate) unsafe fn bind_and_upload<E>(
+        vk_engine: &VulkanEngine,
+        allocator: &mut dyn Allocator,
+        mut texture: Texture,
+        view_type: vk::ImageViewType,
+        upload: impl FnOnce(&mut Texture) -> Result<(), E>,
+    ) -> Result<Self, E>
+    where
+        E: From<SubAllocationError> + From<vk::Result>,
+    {
+        let allocation = match allocator.allocate(
+            &vk_engine.device,
+            texture.get_memory_requirements(&vk_engine.device),
+        ) {
+            Ok(allocation) => allocation,
+            Err(err) => {
+                texture.destroy(&vk_engine.device);
+                return Err(err.into());
+            }
+        };
+
+        let view = texture
+            .bind_memory(vk_engine, allocation.memory, allocation.offset)
+            .map_err(E::from)
+            .and_then(|()| upload(&mut texture))
+            .and_then(|()| {
+                texture
+                    .create_view(&vk_engine.device, view_type, texture.full_range())
+                    .map_err(E::from)
+            });
+        match view {
+            Ok(view) => Ok(Self {
+                texture,
+                view,
+                allocation,
+            }),
+            Err(err) => {
+                texture.destroy(&vk_engine.device);
+                allocator.free(allocation);
+                Err(err)
+            }
+        }
+    }
+}
+
+/// A texture bound to its own memory allocation.
+pub struct TextureWithDedicatedAllocation {
+    pub texture: Texture,
+    pub memory: vk::DeviceMemory,
+    /// True iff the memory was allocated with `VkMemoryDedicatedAllocateInfo` because the
+    /// implementation prefers or requires a dedicated allocation for the image.
+    pub dedicated: bool,
+}
+
+impl TextureWithDedicatedAllocation {
+    /// Allocates memory with the given properties for `texture` and binds it.
+    /// The allocation is made dedicated to the image (`VkMemoryDedicatedAllocateInfo`)
+    /// if the implementation prefers or requires it (`VkMemoryDedicatedRequirements`);
+    /// otherwise, it is a plain allocation of the texture's size.
+    /// Destroys the texture on failure.
+    /// # Safety
+    /// Requires a valid device with sufficient memory and Vulkan 1.1.
+    pub unsafe fn allocate(
+        vk_engine: &VulkanEngine,
+        mut texture: Texture,
+        memory_property_flags: vk::MemoryPropertyFlags,
+    ) -> Result<Self, vk::Result> {
+        let (memory_req, dedicated) = texture.get_dedicated_memory_requirements(&vk_engine.device);
+        let memory_type_index = find_memory_type_index(
+            &memory_req,
+            &vk_engine.device_memory_properties,
+            memory_property_flags,
+        );
+        event!(
+            Level::DEBUG,
+            "Selected memory type index for texture: {} (dedicated: {})",
+            memory_type_index,
+            dedicated
+        );
+
+        let memory = if dedicated {
+            vk_engine.try_allocate_dedicated_image_memory(
+                memory_req,
+                memory_type_index,
+                texture.image,
+            )
+        } else {
+            vk_engine.try_allocate_memory(memory_req, memory_type_index)
+        };
+        let memory = match memory {
+            Ok(memory) => memory,
+            Err(err) => {
+                texture.destroy(&vk_engine.device);
+                return Err(err);
+            }
+        };
+        if let Err(err) = texture.bind_memory(vk_engine, memory, 0) {
+            texture.destroy(&vk_engine.device);
+            vk_engine.device.free_memory(memory, None);
+            return Err(err);
+        }
+
+        Ok(Self {
+            texture,
+            memory,
+            dedicated,
+        })
+    }
+
+    /// Frees the image and memory held by `self`.
+    /// # Safety
+    /// The texture and memory must not be used anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        self.texture.destroy(device);
+        device.free_memory(self.memory, None);
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MipmapError {
+    #[error("Format {0:?} does not support linear blits")]
+    BlitUnsupported(vk::Format),
+    #[error("Format {0:?} cannot be downsampled on the host")]
+    HostDownsamplingUnsupported(vk::Format),
+    #[error("{0} bytes of data do not match the base level size of {1} bytes")]
+    DataSizeMismatch(usize, usize),
+    #[error("Invalid upload: {0}")]
+    Copy(#[from] TextureCopyError),
+}
+
+/// Get the view type covering all array layers of an image: a cube (array) view for cube-compatible
+/// images with a multiple of six layers, an array view for images with several layers,
+/// and a plain view otherwise.
+pub fn default_view_type(
+    image_type: vk::ImageType,
+    flags: vk::ImageCreateFlags,
+    num_array_layers: u32,
+) -> vk::ImageViewType {
+    let cube =
+        flags.contains(vk::ImageCreateFlags::CUBE_COMPATIBLE) && num_array_layers.is_multiple_of(6);
+    match image_type {
+        vk::ImageType::TYPE_1D if num_array_layers > 1 => vk::ImageViewType::TYPE_1D_ARRAY,
+        vk::ImageType::TYPE_1D => vk::ImageViewType::TYPE_1D,
+        vk::ImageType::TYPE_3D => vk::ImageViewType::TYPE_3D,
+        _ if cube && num_array_layers > 6 => vk::ImageViewType::CUBE_ARRAY,
+        _ if cube => vk::ImageViewType::CUBE,
+        _ if num_array_layers > 1 => vk::ImageViewType::TYPE_2D_ARRAY,
+        _ => vk::ImageViewType::TYPE_2D,
+    }
 }
 
 /// Utility function for copying the content of a buffer to an image.
 /// The copy command is issued to the given command buffer.
+/// `aspect_mask` must be a single aspect, e.g. `COLOR` or `DEPTH`.
 /// # Safety
 /// The image has to be in a format suitable for a transfer.
 /// Access to the image must be synchronized properly.
+#[allow(clippy::too_many_arguments)]
 pub unsafe fn copy_buffer_to_image(
     device: &Device,
     command_buffer: vk::CommandBuffer,
     image_staging_buffer: vk::Buffer,
     image: vk::Image,
+    aspect_mask: vk::ImageAspectFlags,
     image_extent: vk::Extent3D,
     num_array_layers: u32,
     buffer_offset: vk::DeviceSize,
@@ -39,7 +472,7 @@ pub unsafe fn copy_buffer_to_image(
             buffer_row_length: 0,
             buffer_image_height: 0,
             image_subresource: vk::ImageSubresourceLayers {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
+                aspect_mask,
                 mip_level: 0,
                 base_array_layer: 0,
                 layer_count: num_array_layers,
@@ -50,6 +483,29 @@ pub unsafe fn copy_buffer_to_image(
     );
 }
 
+/// Utility function for copying the content of a buffer to an image using arbitrary regions,
+/// e.g. one region per mip level.
+/// The copy command is issued to the given command buffer.
+/// # Safety
+/// The image has to be in `TRANSFER_DST_OPTIMAL` layout.
+/// Buffer offsets and image extents of the regions must respect the block size of the image format.
+/// Access to the image must be synchronized properly.
+pub unsafe fn copy_buffer_to_image_regions(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    image_staging_buffer: vk::Buffer,
+    image: vk::Image,
+    regions: &[vk::BufferImageCopy],
+) {
+    device.cmd_copy_buffer_to_image(
+        command_buffer,
+        image_staging_buffer,
+        image,
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        regions,
+    );
+}
+
 /// Utility function for transitioning the layout of the given images using a pipeline barrier.
 /// # Safety
 /// Pipeline barrier requirements must be met.
@@ -72,7 +528,8 @@ pub unsafe fn transition_layout(
 }
 
 impl Texture {
-    /// Create a new texture.
+    /// Create a new texture with the given usage, e.g. `SAMPLED` or `STORAGE`.
+    /// `usage` is extended by `TRANSFER_DST` so the texture can be uploaded to.
     /// # Safety
     /// Device must be valid.
     pub unsafe fn new(
@@ -81,16 +538,139 @@ impl Texture {
         image_type: vk::ImageType,
         extent: vk::Extent3D,
         num_array_layers: u32,
+        usage: vk::ImageUsageFlags,
+    ) -> Result<Self, vk::Result> {
+        Self::create(
+            vk_engine,
+            format,
+            image_type,
+            vk::ImageCreateFlags::empty(),
+            extent,
+            num_array_layers,
+            1,
+            usage | vk::ImageUsageFlags::TRANSFER_DST,
+        )
+    }
+
+    /// Create a new texture with a full mip chain of `floor(log2(max(width, height, depth))) + 1` levels.
+    /// `usage` is extended by `TRANSFER_DST` and `TRANSFER_SRC`, so the mip levels can be generated
+    /// on the device with blits.
+    /// # Safety
+    /// Device must be valid.
+    pub unsafe fn new_with_mip_chain(
+        vk_engine: &VulkanEngine,
+        format: vk::Format,
+        image_type: vk::ImageType,
+        extent: vk::Extent3D,
+        num_array_layers: u32,
+        usage: vk::ImageUsageFlags,
+    ) -> Result<Self, vk::Result> {
+        Self::create(
+            vk_engine,
+            format,
+            image_type,
+            vk::ImageCreateFlags::empty(),
+            extent,
+            num_array_layers,
+            mip_level_count(extent),
+            usage | vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::TRANSFER_SRC,
+        )
+    }
+
+    /// Create a new 2D depth (or depth-stencil) texture usable as a depth-stencil attachment.
+    /// If `sampled` is true, the texture can also be sampled, e.g. as a shadow map.
+    /// # Safety
+    /// Device must be valid. The format must support depth-stencil attachments with optimal tiling.
+    pub unsafe fn new_depth(
+        vk_engine: &VulkanEngine,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        sampled: bool,
+    ) -> Result<Self, vk::Result> {
+        let mut usage = vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT;
+        if sampled {
+            usage |= vk::ImageUsageFlags::SAMPLED;
+        }
+        Self::create(
+            vk_engine,
+            format,
+            vk::ImageType::TYPE_2D,
+            vk::ImageCreateFlags::empty(),
+            vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+            1,
+            1,
+            usage,
+        )
+    }
+
+    /// Create a new cube map texture with square faces of `size` texels, e.g. for skyboxes.
+    /// Each cube has six array layers, one per face in the order +X, -X, +Y, -Y, +Z, -Z (see `cube::CubeFace`);
+    /// with `num_cubes > 1`, the texture is a cube map array.
+    /// `usage` is extended by `TRANSFER_DST` so the texture can be uploaded to.
+    /// # Safety
+    /// Device must be valid. Cube map arrays require the `imageCubeArray` feature to be viewed as such.
+    pub unsafe fn new_cube(
+        vk_engine: &VulkanEngine,
+        format: vk::Format,
+        size: u32,
+        num_cubes: u32,
+        usage: vk::ImageUsageFlags,
+    ) -> Result<Self, vk::Result> {
+        Self::create(
+            vk_engine,
+            format,
+            vk::ImageType::TYPE_2D,
+            vk::ImageCreateFlags::CUBE_COMPATIBLE,
+            vk::Extent3D {
+                width: size,
+                height: size,
+                depth: 1,
+            },
+            6 * num_cubes,
+            1,
+            usage | vk::ImageUsageFlags::TRANSFER_DST,
+        )
+    }
+
+    /// Create a new 3D volume texture, e.g. a colour grading lookup table.
+    /// The data of 3D textures is uploaded slice after slice; use `TextureRegion::slices` to update single slices.
+    /// `usage` is extended by `TRANSFER_DST` so the texture can be uploaded to.
+    /// # Safety
+    /// Device must be valid.
+    pub unsafe fn new_3d(
+        vk_engine: &VulkanEngine,
+        format: vk::Format,
+        extent: vk::Extent3D,
+        usage: vk::ImageUsageFlags,
+    ) -> Result<Self, vk::Result> {
+        Self::new(vk_engine, format, vk::ImageType::TYPE_3D, extent, 1, usage)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn create(
+        vk_engine: &VulkanEngine,
+        format: vk::Format,
+        image_type: vk::ImageType,
+        flags: vk::ImageCreateFlags,
+        extent: vk::Extent3D,
+        num_array_layers: u32,
+        mip_levels: u32,
+        usage: vk::ImageUsageFlags,
     ) -> Result<Self, vk::Result> {
         let image = vk_engine.device.create_image(
             &vk::ImageCreateInfo::builder()
+                .flags(flags)
                 .image_type(image_type)
                 .format(format)
                 .extent(extent)
-                .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+                .usage(usage)
                 .tiling(vk::ImageTiling::OPTIMAL)
                 .samples(vk::SampleCountFlags::TYPE_1)
-                .mip_levels(1)
+                .mip_levels(mip_levels)
                 .array_layers(num_array_layers)
                 .sharing_mode(vk::SharingMode::EXCLUSIVE)
                 .build(),
@@ -100,11 +680,241 @@ impl Texture {
         Ok(Self {
             format,
             image,
+            image_type,
+            flags,
             extent,
             num_array_layers,
+            mip_levels,
+            layout_state: LayoutState::new(mip_levels, num_array_layers),
         })
     }
 
+    /// Get all aspects of the texture's format, used for views and barriers.
+    pub fn aspect_mask(&self) -> vk::ImageAspectFlags {
+        aspect_mask(self.format)
+    }
+
+    /// Get the aspect used for copies if none is specified: the depth aspect for depth-stencil
+    /// formats, as copies can only address a single aspect.
+    pub fn copy_aspect_mask(&self) -> vk::ImageAspectFlags {
+        let aspect_mask = self.aspect_mask();
+        if aspect_mask.contains(vk::ImageAspectFlags::DEPTH) {
+            vk::ImageAspectFlags::DEPTH
+        } else {
+            aspect_mask
+        }
+    }
+
+    /// Get the range covering all mip levels and array layers of `self`.
+    pub fn full_range(&self) -> TextureRange {
+        TextureRange {
+            base_mip_level: 0,
+            level_count: self.mip_levels,
+            base_array_layer: 0,
+            layer_count: self.num_array_layers,
+        }
+    }
+
+    /// Get the view type covering all array layers of `self`, see `default_view_type`.
+    pub fn default_view_type(&self) -> vk::ImageViewType {
+        default_view_type(self.image_type, self.flags, self.num_array_layers)
+    }
+
+    /// Create a view of all mip levels and array layers of `self` with its `default_view_type`,
+    /// e.g. a cube view for cube maps and a 3D view for volume textures.
+    /// # Safety
+    /// Device must be valid.
+    pub unsafe fn create_default_view(&self, device: &Device) -> Result<vk::ImageView, vk::Result> {
+        self.create_view(device, self.default_view_type(), self.full_range())
+    }
+
+    /// Create a view of the given range of `self`, using the texture's format and all of its aspects.
+    /// Views of combined depth-stencil formats used for sampling must only contain a single aspect;
+    /// use `create_aspect_view` for those.
+    /// # Safety
+    /// Device must be valid. The view type must be compatible with the image type and range.
+    pub unsafe fn create_view(
+        &self,
+        device: &Device,
+        view_type: vk::ImageViewType,
+        range: TextureRange,
+    ) -> Result<vk::ImageView, vk::Result> {
+        self.create_aspect_view(device, view_type, range, self.aspect_mask())
+    }
+
+    /// Create a view of the given aspects and range of `self`, e.g. of only the depth aspect.
+    /// # Safety
+    /// Device must be valid. The view type must be compatible with the image type and range.
+    pub unsafe fn create_aspect_view(
+        &self,
+        device: &Device,
+        view_type: vk::ImageViewType,
+        range: TextureRange,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Result<vk::ImageView, vk::Result> {
+        device.create_image_view(
+            &vk::ImageViewCreateInfo::builder()
+                .image(self.image)
+                .format(self.format)
+                .components(vk::ComponentMapping::default())
+                .view_type(view_type)
+                .subresource_range(
+                    vk::ImageSubresourceRange::builder()
+                        .aspect_mask(aspect_mask)
+                        .base_mip_level(range.base_mip_level)
+                        .level_count(range.level_count)
+                        .base_array_layer(range.base_array_layer)
+                        .layer_count(range.layer_count)
+                        .build(),
+                ),
+            None,
+        )
+    }
+
+    /// Check that `region` lies within `self` and get the matching copy command region,
+    /// with the region's data offset relative to `buffer_offset`.
+    /// `data_size` is the size in bytes of the source data; it is checked if the format is known to `format::format_info`.
+    pub fn buffer_image_copy(
+        &self,
+        region: &TextureRegion,
+        buffer_offset: vk::DeviceSize,
+        data_size: vk::DeviceSize,
+    ) -> Result<vk::BufferImageCopy, TextureCopyError> {
+        let level_extent = mip_extent(self.extent, region.mip_level);
+        let within = |offset: i32, extent: u32, size: u32| {
+            offset >= 0 && offset as u64 + extent as u64 <= size as u64
+        };
+        if region.mip_level >= self.mip_levels
+            || region.base_array_layer + region.layer_count > self.num_array_layers
+            || !within(region.offset.x, region.extent.width, level_extent.width)
+            || !within(region.offset.y, region.extent.height, level_extent.height)
+            || !within(region.offset.z, region.extent.depth, level_extent.depth)
+        {
+            return Err(TextureCopyError::RegionOutOfBounds);
+        }
+
+        let aspect_mask = if region.aspect_mask.is_empty() {
+            self.copy_aspect_mask()
+        } else {
+            region.aspect_mask
+        };
+        if aspect_mask.as_raw().count_ones() != 1 || !self.aspect_mask().contains(aspect_mask) {
+            return Err(TextureCopyError::InvalidAspect);
+        }
+        if let Some(info) = format_info(self.format)
+            .and_then(|info| info.aspect_copy_info(self.format, aspect_mask))
+        {
+            // Depth and stencil data always has to start at a multiple of 4.
+            let alignment = match info.kind {
+                FormatKind::Color => info.block_size as u64,
+                _ => 4,
+            };
+            if !region.data_offset.is_multiple_of(alignment) {
+                return Err(TextureCopyError::MisalignedDataOffset);
+            }
+        }
+
+        let copy = vk::BufferImageCopy {
+            buffer_offset: buffer_offset + region.data_offset,
+            buffer_row_length: region.row_length,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask,
+                mip_level: region.mip_level,
+                base_array_layer: region.base_array_layer,
+                layer_count: region.layer_count,
+            },
+            image_offset: region.offset,
+            image_extent: region.extent,
+        };
+        self.validate_copies(&[copy], buffer_offset + data_size)?;
+        Ok(copy)
+    }
+
+    /// Get the end of the buffer data read by `copy`, i.e. its buffer offset plus the size of its data,
+    /// or `None` if the format is unknown or doesn't have the copy's aspect.
+    pub fn copy_data_end(&self, copy: &vk::BufferImageCopy) -> Option<vk::DeviceSize> {
+        let info = format_info(self.format)?
+            .aspect_copy_info(self.format, copy.image_subresource.aspect_mask)?;
+        let data_extent = vk::Extent3D {
+            width: copy.buffer_row_length.max(copy.image_extent.width),
+            height: copy.buffer_image_height.max(copy.image_extent.height),
+            depth: copy.image_extent.depth,
+        };
+        Some(
+            copy.buffer_offset
+                + info.image_size(data_extent) * copy.image_subresource.layer_count as u64,
+        )
+    }
+
+    /// Check that the data of all copies lies within a buffer of `buffer_size` bytes.
+    /// Copies to formats unknown to `format::format_info` are not checked.
+    pub fn validate_copies(
+        &self,
+        copies: &[vk::BufferImageCopy],
+        buffer_size: vk::DeviceSize,
+    ) -> Result<(), TextureCopyError> {
+        let out_of_bounds = copies
+            .iter()
+            .filter_map(|copy| self.copy_data_end(copy))
+            .any(|end| end > buffer_size);
+        if out_of_bounds {
+            Err(TextureCopyError::DataOutOfBounds)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Records a copy of the given regions from `staging_buffer` to `self`, keeping the rest of the
+    /// texture's content. The whole texture is transitioned from its tracked state to `TRANSFER_DST_OPTIMAL`
+    /// before the copy and to `dst` afterwards. Use `buffer_image_copy` to create the regions.
+    /// When updating many textures at once, `UploadBatch::update_texture` batches the barriers.
+    /// # Safety
+    /// `command_buffer` must be in the recording state and outside of a render pass.
+    /// The tracked layout state of `self` must be up to date.
+    pub unsafe fn record_update(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        staging_buffer: vk::Buffer,
+        regions: &[vk::BufferImageCopy],
+        dst: ImageAccess,
+    ) {
+        self.transition_to(device, command_buffer, ImageAccess::TRANSFER_DST);
+        copy_buffer_to_image_regions(device, command_buffer, staging_buffer, self.image, regions);
+        self.transition_to(device, command_buffer, dst);
+    }
+
+    /// Records a transition of all mip levels and layers of `self` from their tracked state to `dst`.
+    /// The barrier's source stages, accesses and old layout are taken from the tracked state,
+    /// which is updated to `dst`. Use `LayoutTransitions` to transition many textures with a single barrier.
+    /// # Safety
+    /// `command_buffer` must be in the recording state and outside of a render pass.
+    /// The tracked layout state of `self` must be up to date.
+    pub unsafe fn transition_to(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        dst: ImageAccess,
+    ) {
+        self.transition_range_to(device, command_buffer, self.full_range(), dst);
+    }
+
+    /// Like `transition_to`, but only transitions the given range of `self`.
+    /// # Safety
+    /// See `transition_to`.
+    pub unsafe fn transition_range_to(
+        &mut self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        range: TextureRange,
+        dst: ImageAccess,
+    ) {
+        let mut transitions = LayoutTransitions::new();
+        transitions.add(self, range, dst);
+        transitions.record(device, command_buffer);
+    }
+
     /// Get the memory requirements for this texture.
     /// # Safety
     /// Device and image must be valid.
@@ -112,6 +922,24 @@ impl Texture {
         device.get_image_memory_requirements(self.image)
     }
 
+    /// Get the memory requirements for this texture and whether the implementation prefers
+    /// or requires a dedicated allocation for it (`VkMemoryDedicatedRequirements`).
+    /// # Safety
+    /// Device and image must be valid. Requires Vulkan 1.1.
+    pub unsafe fn get_dedicated_memory_requirements(
+        &self,
+        device: &Device,
+    ) -> (vk::MemoryRequirements, bool) {
+        let mut dedicated_req = vk::MemoryDedicatedRequirements::default();
+        let mut memory_req = vk::MemoryRequirements2::builder().push_next(&mut dedicated_req);
+        let info = vk::ImageMemoryRequirementsInfo2::builder().image(self.image);
+        device.get_image_memory_requirements2(&info, &mut memory_req);
+        let memory_req = memory_req.memory_requirements;
+        let dedicated = dedicated_req.prefers_dedicated_allocation == vk::TRUE
+            || dedicated_req.requires_dedicated_allocation == vk::TRUE;
+        (memory_req, dedicated)
+    }
+
     /// Bind the given memory to this texture.
     /// # Safety
     /// Device and memory region must be valid.
@@ -126,13 +954,60 @@ impl Texture {
             .bind_image_memory(self.image, memory, offset)
     }
 
-    /// Create an image memory barrier for the image resource held by `self`.
+    /// Create an image memory barrier for the image resource held by `self`, covering all mip levels.
     pub fn get_transition_layout_image_memory_barrier(
         &self,
         src_access_mask: vk::AccessFlags,
         dst_access_mask: vk::AccessFlags,
         old_layout: vk::ImageLayout,
         new_layout: vk::ImageLayout,
+    ) -> vk::ImageMemoryBarrier {
+        self.get_mip_levels_image_memory_barrier(
+            0,
+            self.mip_levels,
+            src_access_mask,
+            dst_access_mask,
+            old_layout,
+            new_layout,
+        )
+    }
+
+    /// Create an image memory barrier for `level_count` mip levels of `self`, starting at `base_mip_level`.
+    pub fn get_mip_levels_image_memory_barrier(
+        &self,
+        base_mip_level: u32,
+        level_count: u32,
+        src_access_mask: vk::AccessFlags,
+        dst_access_mask: vk::AccessFlags,
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+    ) -> vk::ImageMemoryBarrier {
+        self.get_aspect_image_memory_barrier(
+            self.aspect_mask(),
+            TextureRange {
+                base_mip_level,
+                level_count,
+                ..self.full_range()
+            },
+            src_access_mask,
+            dst_access_mask,
+            old_layout,
+            new_layout,
+        )
+    }
+
+    /// Create an image memory barrier for the given aspects and range of `self`.
+    /// With `separateDepthStencilLayouts` (Vulkan 1.2), the depth and stencil aspects of a combined
+    /// depth-stencil texture can be transitioned separately, e.g. `DEPTH` to `DEPTH_READ_ONLY_OPTIMAL`
+    /// while `STENCIL` goes to `STENCIL_ATTACHMENT_OPTIMAL`.
+    pub fn get_aspect_image_memory_barrier(
+        &self,
+        aspect_mask: vk::ImageAspectFlags,
+        range: TextureRange,
+        src_access_mask: vk::AccessFlags,
+        dst_access_mask: vk::AccessFlags,
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
     ) -> vk::ImageMemoryBarrier {
         vk::ImageMemoryBarrier::builder()
             .src_access_mask(src_access_mask)
@@ -142,11 +1017,11 @@ impl Texture {
             .image(self.image)
             .subresource_range(
                 vk::ImageSubresourceRange::builder()
-                    .aspect_mask(vk::ImageAspectFlags::COLOR)
-                    .base_mip_level(0)
-                    .level_count(1)
-                    .base_array_layer(0)
-                    .layer_count(self.num_array_layers)
+                    .aspect_mask(aspect_mask)
+                    .base_mip_level(range.base_mip_level)
+                    .level_count(range.level_count)
+                    .base_array_layer(range.base_array_layer)
+                    .layer_count(range.layer_count)
                     .build(),
             )
             .build()
@@ -154,64 +1029,296 @@ impl Texture {
 
     /// Utility method for copying data from a staging buffer to `self`.
     /// Performs a layout transition before and after copying with a single-use command buffer (using VulkanEngine::one_time_submit).
-    /// Assumes that the image will be used only as a sample source in a fragment shader.
+    /// Afterwards, the image is in `dst.layout` and the copy is made visible to `dst`'s stages and accesses,
+    /// e.g. `ImageAccess::FRAGMENT_SHADER_READ` for textures sampled in a fragment shader.
     /// This will use one pipeline barrier for every call.
-    /// When dealing with many images, a manual implementation may be beneficial for performance
-    /// by reducing the number of pipeline barriers
+    /// When dealing with many images, `UploadBatch` may be beneficial for performance
+    /// by reducing the number of pipeline barriers and submits.
     ///
     /// # Safety
     /// Must be called on the thread able to submit command buffers to the given command pool.
-    /// The default values for synchronization used must match the actual usage of the buffer and
-    /// image; otherwise, race conditions on the device may occur.
+    /// `dst` must match the actual usage of the image; otherwise, race conditions on the device may occur.
     pub unsafe fn copy_staging_to_image<T: Copy>(
         &mut self,
         vk_engine: &VulkanEngine,
         command_pool: vk::CommandPool,
         image_staging_buffer: &StagingBuffer<T>,
         buffer_offset: vk::DeviceSize,
-    ) {
-        vk_engine.one_time_submit(command_pool, |command_buffer| {
-            let barrier = self.get_transition_layout_image_memory_barrier(
-                vk::AccessFlags::empty(),
-                vk::AccessFlags::TRANSFER_WRITE,
-                ImageLayout::UNDEFINED,
-                ImageLayout::TRANSFER_DST_OPTIMAL,
-            );
+        dst: ImageAccess,
+    ) -> Result<(), TextureCopyError> {
+        self.copy_staging_regions_to_image(
+            vk_engine,
+            command_pool,
+            image_staging_buffer,
+            &[vk::BufferImageCopy {
+                buffer_offset,
+                buffer_row_length: 0,
+                buffer_image_height: 0,
+                image_subresource: vk::ImageSubresourceLayers {
+                    aspect_mask: self.copy_aspect_mask(),
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count: self.num_array_layers,
+                },
+                image_offset: vk::Offset3D::default(),
+                image_extent: self.extent,
+            }],
+            dst,
+        )
+    }
 
-            transition_layout(
-                &vk_engine.device,
-                command_buffer,
-                &[barrier],
-                vk::PipelineStageFlags::TOP_OF_PIPE,
-                vk::PipelineStageFlags::TRANSFER,
-            );
+    /// Like `copy_staging_to_image`, but copies the given regions, e.g. one region per mip level.
+    /// All mip levels and layers of `self` are transitioned and their previous content is discarded,
+    /// so they should all be covered by the regions.
+    /// Fails without recording anything if a region reads beyond the end of the staging buffer.
+    ///
+    /// # Safety
+    /// See `copy_staging_to_image`. Buffer offsets and image extents of the regions must respect
+    /// the block size of the image format.
+    pub unsafe fn copy_staging_regions_to_image<T: Copy>(
+        &mut self,
+        vk_engine: &VulkanEngine,
+        command_pool: vk::CommandPool,
+        image_staging_buffer: &StagingBuffer<T>,
+        regions: &[vk::BufferImageCopy],
+        dst: ImageAccess,
+    ) -> Result<(), TextureCopyError> {
+        let buffer = &image_staging_buffer.buffer;
+        self.validate_copies(regions, buffer.length * std::mem::size_of::<T>() as u64)?;
+
+        self.layout_state.discard(self.full_range());
+        let mut pre_transitions = LayoutTransitions::new();
+        pre_transitions.add(self, self.full_range(), ImageAccess::TRANSFER_DST);
+        let mut post_transitions = LayoutTransitions::new();
+        post_transitions.add(self, self.full_range(), dst);
+
+        vk_engine.one_time_submit(command_pool, |command_buffer| {
+            pre_transitions.record(&vk_engine.device, command_buffer);
 
             // Copy buffer to image
-            copy_buffer_to_image(
+            copy_buffer_to_image_regions(
                 &vk_engine.device,
                 command_buffer,
                 image_staging_buffer.buffer.buffer,
                 self.image,
-                self.extent,
-                self.num_array_layers,
-                buffer_offset,
+                regions,
             );
 
-            let barrier = self.get_transition_layout_image_memory_barrier(
-                vk::AccessFlags::TRANSFER_WRITE,
-                vk::AccessFlags::SHADER_READ,
-                ImageLayout::TRANSFER_DST_OPTIMAL,
-                ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            post_transitions.record(&vk_engine.device, command_buffer);
+        });
+        Ok(())
+    }
+
+    /// Check whether the mip levels of `self` can be generated on the device with linear blits.
+    /// # Safety
+    /// Instance and physical device must be valid.
+    pub unsafe fn supports_blit_mipmaps(
+        &self,
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> bool {
+        let properties =
+            instance.get_physical_device_format_properties(physical_device, self.format);
+        properties.optimal_tiling_features.contains(
+            vk::FormatFeatureFlags::BLIT_SRC
+                | vk::FormatFeatureFlags::BLIT_DST
+                | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+        )
+    }
+
+    /// Records a blit chain generating all mip levels of `self` from mip level 0.
+    /// Mip level 0 has to hold the image data; the previous content of the other levels is discarded.
+    /// The source of the transitions is taken from the tracked layout state.
+    /// Afterwards, all levels are in `dst.layout`, with the writes made visible to `dst`'s stages and accesses.
+    /// Fails if the format doesn't support linear blits; see `upload_with_mipmaps` for a host-side fallback.
+    /// # Safety
+    /// `command_buffer` must be in the recording state and outside of a render pass.
+    /// The texture must have been created with `TRANSFER_SRC` usage. The tracked layout state of `self`
+    /// must be up to date.
+    pub unsafe fn generate_mipmaps(
+        &mut self,
+        instance: &Instance,
+        vk_engine: &VulkanEngine,
+        command_buffer: vk::CommandBuffer,
+        dst: ImageAccess,
+    ) -> Result<(), MipmapError> {
+        if !self.supports_blit_mipmaps(instance, vk_engine.physical_device) {
+            return Err(MipmapError::BlitUnsupported(self.format));
+        }
+
+        let device = &vk_engine.device;
+        let aspect_mask = self.aspect_mask();
+        let num_array_layers = self.num_array_layers;
+        let subresource = |mip_level| vk::ImageSubresourceLayers {
+            aspect_mask,
+            mip_level,
+            base_array_layer: 0,
+            layer_count: num_array_layers,
+        };
+        let far_corner = |extent: vk::Extent3D| vk::Offset3D {
+            x: extent.width as i32,
+            y: extent.height as i32,
+            z: extent.depth as i32,
+        };
+        let full_range = self.full_range();
+        let levels = |base_mip_level, level_count| TextureRange {
+            base_mip_level,
+            level_count,
+            ..full_range
+        };
+
+        let dst_levels = levels(1, self.mip_levels - 1);
+        self.layout_state.discard(dst_levels);
+        self.transition_range_to(
+            device,
+            command_buffer,
+            dst_levels,
+            ImageAccess::TRANSFER_DST,
+        );
+
+        for mip_level in 1..self.mip_levels {
+            let src_level = mip_level - 1;
+            self.transition_range_to(
+                device,
+                command_buffer,
+                levels(src_level, 1),
+                ImageAccess::TRANSFER_SRC,
             );
 
-            transition_layout(
-                &vk_engine.device,
+            device.cmd_blit_image(
                 command_buffer,
-                &[barrier],
-                vk::PipelineStageFlags::TRANSFER,
-                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                self.image,
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
+                self.image,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[vk::ImageBlit {
+                    src_subresource: subresource(src_level),
+                    src_offsets: [
+                        vk::Offset3D::default(),
+                        far_corner(mip_extent(self.extent, src_level)),
+                    ],
+                    dst_subresource: subresource(mip_level),
+                    dst_offsets: [
+                        vk::Offset3D::default(),
+                        far_corner(mip_extent(self.extent, mip_level)),
+                    ],
+                }],
+                vk::Filter::LINEAR,
             );
+
+            self.transition_range_to(device, command_buffer, levels(src_level, 1), dst);
+        }
+
+        self.transition_range_to(device, command_buffer, levels(self.mip_levels - 1, 1), dst);
+
+        Ok(())
+    }
+
+    /// Uploads `data` to mip level 0 of `self` and fills all other mip levels with a single-use
+    /// command buffer (using VulkanEngine::one_time_submit). `data` holds all array layers, tightly packed;
+    /// bytes past the end of mip level 0 are ignored.
+    /// Mip levels are blitted on the device if the format supports it; otherwise, they are generated
+    /// on the host, which is supported for formats with 8 bits per channel.
+    /// Like `copy_staging_to_image`, transitions the image to `dst.layout` and synchronizes with `dst`.
+    /// # Safety
+    /// Must be called on the thread able to submit command buffers to the given command pool.
+    /// The image must not be in use.
+    pub unsafe fn upload_with_mipmaps<T: Copy>(
+        &mut self,
+        instance: &Instance,
+        vk_engine: &VulkanEngine,
+        command_pool: vk::CommandPool,
+        data: &[T],
+        dst: ImageAccess,
+    ) -> Result<(), MipmapError> {
+        let data =
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data));
+        let level = TextureRegion::mip_level(self, 0);
+        let copy = self.buffer_image_copy(&level, 0, data.len() as _)?;
+        // Only the base level is read from `data`; trailing bytes are ignored.
+        let data = match self.copy_data_end(&copy) {
+            Some(end) => &data[..end as usize],
+            None => data,
+        };
+
+        let blit = self.supports_blit_mipmaps(instance, vk_engine.physical_device);
+        let chain = if blit {
+            None
+        } else {
+            let channels = u8_channel_count(self.format)
+                .ok_or(MipmapError::HostDownsamplingUnsupported(self.format))?;
+            Some(MipChain::generate(
+                data,
+                self.extent,
+                self.num_array_layers,
+                channels,
+                self.mip_levels,
+            )?)
+        };
+        let staging_data = chain.as_ref().map_or(data, |chain| &chain.data);
+
+        let mut staging_buffer = StagingBufferWithDedicatedAllocation::<u8>::allocate(
+            vk_engine,
+            staging_data.len() as _,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::SharingMode::EXCLUSIVE,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        staging_buffer.buffer.write(staging_data, 0);
+
+        let mut result = Ok(());
+        self.layout_state.discard(self.full_range());
+        vk_engine.one_time_submit(command_pool, |command_buffer| {
+            self.transition_to(&vk_engine.device, command_buffer, ImageAccess::TRANSFER_DST);
+
+            match &chain {
+                None => {
+                    copy_buffer_to_image(
+                        &vk_engine.device,
+                        command_buffer,
+                        staging_buffer.buffer.buffer.buffer,
+                        self.image,
+                        self.copy_aspect_mask(),
+                        self.extent,
+                        self.num_array_layers,
+                        0,
+                    );
+                    result = self.generate_mipmaps(instance, vk_engine, command_buffer, dst);
+                }
+                Some(chain) => {
+                    let regions = chain
+                        .levels
+                        .iter()
+                        .enumerate()
+                        .map(|(mip_level, level)| vk::BufferImageCopy {
+                            buffer_offset: level.offset as _,
+                            buffer_row_length: 0,
+                            buffer_image_height: 0,
+                            image_subresource: vk::ImageSubresourceLayers {
+                                aspect_mask: self.copy_aspect_mask(),
+                                mip_level: mip_level as u32,
+                                base_array_layer: 0,
+                                layer_count: self.num_array_layers,
+                            },
+                            image_offset: vk::Offset3D::default(),
+                            image_extent: level.extent,
+                        })
+                        .collect::<Vec<_>>();
+                    vk_engine.device.cmd_copy_buffer_to_image(
+                        command_buffer,
+                        staging_buffer.buffer.buffer.buffer,
+                        self.image,
+                        ImageLayout::TRANSFER_DST_OPTIMAL,
+                        &regions,
+                    );
+
+                    self.transition_to(&vk_engine.device, command_buffer, dst);
+                }
+            }
         });
+
+        staging_buffer.destroy(&vk_engine.device);
+        result
     }
 
     /// Free the image resource held by `self`.
@@ -221,3 +1328,103 @@ impl Texture {
         device.destroy_image(self.image, None);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_bounds() {
+        let texture = Texture {
+            format: vk::Format::R8G8B8A8_UNORM,
+            image: vk::Image::null(),
+            image_type: vk::ImageType::TYPE_2D,
+            flags: vk::ImageCreateFlags::empty(),
+            extent: vk::Extent3D {
+                width: 16,
+                height: 16,
+                depth: 1,
+            },
+            num_array_layers: 4,
+            mip_levels: 5,
+            layout_state: LayoutState::new(5, 4),
+        };
+
+        let region = TextureRegion {
+            row_length: 32,
+            ..TextureRegion::rect(
+                3,
+                vk::Offset2D { x: 8, y: 8 },
+                vk::Extent2D {
+                    width: 8,
+                    height: 8,
+                },
+            )
+        };
+        let copy = texture.buffer_image_copy(&region, 64, 32 * 8 * 4).unwrap();
+        assert_eq!(copy.buffer_offset, 64);
+        assert_eq!(copy.buffer_row_length, 32);
+        let misaligned = TextureRegion {
+            data_offset: 2,
+            ..region
+        };
+        assert!(matches!(
+            texture.buffer_image_copy(&misaligned, 0, 32 * 8 * 4 + 2),
+            Err(TextureCopyError::MisalignedDataOffset)
+        ));
+        assert!(matches!(
+            texture.buffer_image_copy(&region, 0, 32 * 8 * 4 - 1),
+            Err(TextureCopyError::DataOutOfBounds)
+        ));
+
+        let region = TextureRegion {
+            offset: vk::Offset3D { x: 1, y: 0, z: 0 },
+            ..TextureRegion::mip_level(&texture, 2)
+        };
+        assert!(matches!(
+            texture.buffer_image_copy(&region, 0, u64::MAX / 2),
+            Err(TextureCopyError::RegionOutOfBounds)
+        ));
+    }
+
+    #[test]
+    fn depth_stencil_aspects() {
+        let texture = Texture {
+            format: vk::Format::D24_UNORM_S8_UINT,
+            image: vk::Image::null(),
+            image_type: vk::ImageType::TYPE_2D,
+            flags: vk::ImageCreateFlags::empty(),
+            extent: vk::Extent3D {
+                width: 4,
+                height: 4,
+                depth: 1,
+            },
+            num_array_layers: 1,
+            mip_levels: 1,
+            layout_state: LayoutState::new(1, 1),
+        };
+        assert_eq!(
+            texture.aspect_mask(),
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        );
+        assert_eq!(texture.copy_aspect_mask(), vk::ImageAspectFlags::DEPTH);
+
+        let region = TextureRegion {
+            aspect_mask: texture.aspect_mask(),
+            ..TextureRegion::layer(&texture, 0)
+        };
+        assert!(matches!(
+            texture.buffer_image_copy(&region, 0, 64),
+            Err(TextureCopyError::InvalidAspect)
+        ));
+        let region = TextureRegion {
+            aspect_mask: vk::ImageAspectFlags::STENCIL,
+            ..region
+        };
+        let copy = texture.buffer_image_copy(&region, 0, 64).unwrap();
+        assert_eq!(
+            copy.image_subresource.aspect_mask,
+            vk::ImageAspectFlags::STENCIL
+        );
+    }
+}
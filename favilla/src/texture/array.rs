@@ -0,0 +1,305 @@
+//! Building a 2D texture array from many images, e.g. the tiles of a tileset,
+//! so they can be bound as a single `sampler2DArray`.
+use crate::allocator::{Allocator, SubAllocationError};
+use crate::format::format_info;
+use crate::texture::mipmap::u8_channel_count;
+use crate::texture::{ImageAccess, LoadedTexture, Texture, TextureCopyError, TextureRegion};
+use crate::upload_batch::UploadBatch;
+use crate::vk_engine::VulkanEngine;
+use ash::vk;
+use std::collections::HashMap;
+use std::hash::Hash;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TextureArrayError {
+    #[error("An image of {0}x{1} texels does not match the layer extent")]
+    ExtentMismatch(u32, u32),
+    #[error("Images in format {0:?} cannot be resized on the host")]
+    ResizeUnsupported(vk::Format),
+    #[error("The image data does not match its extent")]
+    InvalidData,
+    #[error("Format {0:?} is not known to favilla")]
+    UnknownFormat(vk::Format),
+    #[error("A layer with the same key has already been added")]
+    DuplicateKey,
+    #[error("The texture array has no layers")]
+    Empty,
+    #[error("A layer extent of {0}x{1} texels is empty")]
+    InvalidExtent(u32, u32),
+    #[error("Invalid upload: {0}")]
+    Copy(#[from] TextureCopyError),
+    #[error("Failed to allocate texture memory: {0}")]
+    Allocation(#[from] SubAllocationError),
+    #[error("Vulkan error: {0}")]
+    Vulkan(#[from] vk::Result),
+}
+
+/// How images whose extent differs from the layer extent are handled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExtentMismatch {
+    /// `add` fails with `TextureArrayError::ExtentMismatch`.
+    Reject,
+    /// The image is resized with bilinear filtering. Supported for formats with 8 bits per channel;
+    /// channels are filtered independently, so sRGB formats are filtered in gamma space.
+    Resize,
+}
+
+/// Collects images as the layers of a 2D texture array. The layer data is laid out tightly packed,
+/// layer after layer, and uploaded with one copy region per layer.
+/// Each layer is identified by a key, e.g. the name of a tile.
+pub struct TextureArrayBuilder<K> {
+    format: vk::Format,
+    extent: vk::Extent2D,
+    pub extent_mismatch: ExtentMismatch,
+    data: Vec<u8>,
+    layers: HashMap<K, u32>,
+    layer_count: u32,
+}
+
+/// A texture array built by `TextureArrayBuilder`, together with the layer index of every key.
+pub struct TextureArray<K> {
+    pub texture: LoadedTexture,
+    pub layers: HashMap<K, u32>,
+}
+
+impl<K: Eq + Hash> TextureArrayBuilder<K> {
+    /// Create a new, empty builder for layers of the given format and extent.
+    /// Fails if the extent is empty.
+    pub fn new(
+        format: vk::Format,
+        extent: vk::Extent2D,
+        extent_mismatch: ExtentMismatch,
+    ) -> Result<Self, TextureArrayError> {
+        if extent.width == 0 || extent.height == 0 {
+            return Err(TextureArrayError::InvalidExtent(
+                extent.width,
+                extent.height,
+            ));
+        }
+        Ok(Self {
+            format,
+            extent,
+            extent_mismatch,
+            data: vec![],
+            layers: HashMap::new(),
+            layer_count: 0,
+        })
+    }
+
+    /// Get the format of the layers.
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    /// Get the extent of the layers.
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    /// Get the number of layers added so far.
+    pub fn layer_count(&self) -> u32 {
+        self.layer_count
+    }
+
+    /// Get the tightly packed data of all layers added so far.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Add `data`, an image with the given extent in the builder's format, as a new layer.
+    /// Returns the index of the new layer. Fails for formats unknown to `format::format_info`,
+    /// whose data size can't be checked, and for empty images.
+    pub fn add(
+        &mut self,
+        key: K,
+        data: &[u8],
+        extent: vk::Extent2D,
+    ) -> Result<u32, TextureArrayError> {
+        if self.layers.contains_key(&key) {
+            return Err(TextureArrayError::DuplicateKey);
+        }
+        let extent_3d = |extent: vk::Extent2D| vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        };
+        let info = format_info(self.format).ok_or(TextureArrayError::UnknownFormat(self.format))?;
+        if extent.width == 0
+            || extent.height == 0
+            || info.image_size(extent_3d(extent)) != data.len() as u64
+        {
+            return Err(TextureArrayError::InvalidData);
+        }
+
+        if extent == self.extent {
+            self.data.extend_from_slice(data);
+        } else {
+            if self.extent_mismatch == ExtentMismatch::Reject {
+                return Err(TextureArrayError::ExtentMismatch(
+                    extent.width,
+                    extent.height,
+                ));
+            }
+            let channels = u8_channel_count(self.format)
+                .ok_or(TextureArrayError::ResizeUnsupported(self.format))?;
+            resize_bilinear(data, extent, self.extent, channels, &mut self.data);
+        }
+
+        let layer = self.layer_count;
+        self.layers.insert(key, layer);
+        self.layer_count += 1;
+        Ok(layer)
+    }
+
+    /// Get the copy regions of all layers, one per layer with the layer's offset in `data`.
+    pub fn regions(&self, texture: &Texture) -> Vec<TextureRegion> {
+        let layer_size =
+            self.data.len() as vk::DeviceSize / self.layer_count.max(1) as vk::DeviceSize;
+        (0..self.layer_count)
+            .map(|layer| TextureRegion {
+                data_offset: layer as vk::DeviceSize * layer_size,
+                ..TextureRegion::layer(texture, layer)
+            })
+            .collect()
+    }
+
+    /// Creates a 2D array texture with one layer per added image, binds memory from `allocator`
+    /// and uploads all layers with a single-use command buffer (using VulkanEngine::one_time_submit).
+    /// The texture is viewed as a 2D array, even if it only has a single layer.
+    /// `dst` describes how the texture is used afterwards; the texture is created with the usage it requires.
+    /// # Safety
+    /// Must be called on the thread able to submit command buffers to the given command pool.
+    pub unsafe fn build(
+        self,
+        vk_engine: &VulkanEngine,
+        command_pool: vk::CommandPool,
+        allocator: &mut dyn Allocator,
+        dst: ImageAccess,
+    ) -> Result<TextureArray<K>, TextureArrayError> {
+        if self.layer_count == 0 {
+            return Err(TextureArrayError::Empty);
+        }
+
+        let texture = Texture::new(
+            vk_engine,
+            self.format,
+            vk::ImageType::TYPE_2D,
+            vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            },
+            self.layer_count,
+            dst.required_usage(),
+        )?;
+        let texture = LoadedTexture::bind_and_upload(
+            vk_engine,
+            allocator,
+            texture,
+            vk::ImageViewType::TYPE_2D_ARRAY,
+            |texture| {
+                let regions = self.regions(texture);
+                let mut upload_batch = UploadBatch::new();
+                upload_batch.update_texture(&self.data, texture, &regions, dst)?;
+                upload_batch.submit(vk_engine, command_pool);
+                Ok::<_, TextureArrayError>(())
+            },
+        )?;
+
+        Ok(TextureArray {
+            texture,
+            layers: self.layers,
+        })
+    }
+}
+
+/// Appends `src` with extent `src_extent`, resized to `dst_extent` with bilinear filtering, to `dst`.
+fn resize_bilinear(
+    src: &[u8],
+    src_extent: vk::Extent2D,
+    dst_extent: vk::Extent2D,
+    channels: usize,
+    dst: &mut Vec<u8>,
+) {
+    // Maps the centre of a destination texel to the two nearest source texels and the weight of the second.
+    let sample = |dst_coordinate: u32, dst_size: u32, src_size: u32| {
+        let position = (dst_coordinate as f32 + 0.5) * src_size as f32 / dst_size as f32 - 0.5;
+        let position = position.clamp(0.0, (src_size - 1) as f32);
+        let first = position.floor() as u32;
+        let second = (first + 1).min(src_size - 1);
+        (first, second, position - first as f32)
+    };
+    let texel = |x: u32, y: u32, channel: usize| {
+        src[(y as usize * src_extent.width as usize + x as usize) * channels + channel] as f32
+    };
+
+    for y in 0..dst_extent.height {
+        let (y0, y1, wy) = sample(y, dst_extent.height, src_extent.height);
+        for x in 0..dst_extent.width {
+            let (x0, x1, wx) = sample(x, dst_extent.width, src_extent.width);
+            for channel in 0..channels {
+                let top = texel(x0, y0, channel) * (1.0 - wx) + texel(x1, y0, channel) * wx;
+                let bottom = texel(x0, y1, channel) * (1.0 - wx) + texel(x1, y1, channel) * wx;
+                dst.push((top * (1.0 - wy) + bottom * wy).round() as u8);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_and_resizing() {
+        let extent = vk::Extent2D {
+            width: 2,
+            height: 2,
+        };
+        let mut builder =
+            TextureArrayBuilder::new(vk::Format::R8_UNORM, extent, ExtentMismatch::Resize).unwrap();
+        assert_eq!(builder.add("grass", &[10, 20, 30, 40], extent).unwrap(), 0);
+        let small = vk::Extent2D {
+            width: 1,
+            height: 1,
+        };
+        assert_eq!(builder.add("water", &[200], small).unwrap(), 1);
+        assert!(matches!(
+            builder.add("grass", &[0; 4], extent),
+            Err(TextureArrayError::DuplicateKey)
+        ));
+        assert!(matches!(
+            builder.add("sand", &[0; 3], extent),
+            Err(TextureArrayError::InvalidData)
+        ));
+        let empty = vk::Extent2D {
+            width: 0,
+            height: 2,
+        };
+        assert!(matches!(
+            builder.add("sand", &[], empty),
+            Err(TextureArrayError::InvalidData)
+        ));
+        assert!(matches!(
+            TextureArrayBuilder::<&str>::new(vk::Format::R8_UNORM, empty, ExtentMismatch::Reject),
+            Err(TextureArrayError::InvalidExtent(0, 2))
+        ));
+        assert_eq!(builder.layer_count(), 2);
+        assert_eq!(builder.data(), [10, 20, 30, 40, 200, 200, 200, 200]);
+
+        builder.extent_mismatch = ExtentMismatch::Reject;
+        assert!(matches!(
+            builder.add("sand", &[0], small),
+            Err(TextureArrayError::ExtentMismatch(1, 1))
+        ));
+
+        let mut builder =
+            TextureArrayBuilder::new(vk::Format::UNDEFINED, extent, ExtentMismatch::Reject)
+                .unwrap();
+        assert!(matches!(
+            builder.add("grass", &[0; 4], extent),
+            Err(TextureArrayError::UnknownFormat(vk::Format::UNDEFINED))
+        ));
+    }
+}
@@ -0,0 +1,469 @@
+//! Loading pre-compressed textures from KTX2 (`ktx2` feature) and DDS (`dds` feature) files.
+//!
+//! Containers are parsed into `TextureData`, which holds the texel data of all mip levels and
+//! array layers together with the matching copy regions. Uploading checks whether the device
+//! supports the format first, so callers can fall back to another file (e.g. an uncompressed one)
+//! without having created any resources. There is no other fallback: compressed data is never decoded
+//! on the host, and `upload` returns `ContainerError::UnsupportedFormat` for unsupported formats.
+use crate::allocator::{get_aligned_offset, Allocator, SubAllocationError};
+use crate::buffer::StagingBufferWithDedicatedAllocation;
+use crate::format::{format_info, FormatInfo};
+use crate::texture::mipmap::mip_extent;
+use crate::texture::{ImageAccess, LoadedTexture, Texture, TextureCopyError};
+use crate::vk_engine::VulkanEngine;
+use ash::{vk, Instance};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContainerError {
+    #[cfg(feature = "ktx2")]
+    #[error("Invalid KTX2 file: {0}")]
+    Ktx2(#[from] ktx2::ParseError),
+    #[cfg(feature = "dds")]
+    #[error("Invalid DDS file: {0}")]
+    Dds(#[from] ddsfile::Error),
+    #[error("Supercompressed KTX2 files are not supported")]
+    Supercompressed,
+    #[error("The container's format is not known to favilla")]
+    UnknownFormat,
+    #[error("Format {0:?} is not supported by the device")]
+    UnsupportedFormat(vk::Format),
+    #[error("The container's data does not match its header")]
+    InvalidData,
+    #[error("Invalid copy regions: {0}")]
+    Copy(#[from] TextureCopyError),
+    #[error("Failed to allocate texture memory: {0}")]
+    Allocation(#[from] SubAllocationError),
+    #[error("Vulkan error: {0}")]
+    Vulkan(#[from] vk::Result),
+}
+
+/// The texel data of all mip levels and array layers of a texture, ready to be uploaded.
+pub struct TextureData {
+    pub format: vk::Format,
+    pub image_type: vk::ImageType,
+    /// `CUBE_COMPATIBLE` for cube maps, empty otherwise.
+    pub flags: vk::ImageCreateFlags,
+    pub extent: vk::Extent3D,
+    /// The number of array layers; cube map faces are counted as separate layers.
+    pub num_array_layers: u32,
+    pub mip_levels: u32,
+    pub data: Vec<u8>,
+    /// Copy regions from `data` into the image, with block-aligned buffer offsets.
+    pub regions: Vec<vk::BufferImageCopy>,
+}
+
+impl TextureData {
+    fn new(
+        format: vk::Format,
+        image_type: vk::ImageType,
+        flags: vk::ImageCreateFlags,
+        extent: vk::Extent3D,
+        num_array_layers: u32,
+        mip_levels: u32,
+    ) -> Self {
+        Self {
+            format,
+            image_type,
+            flags,
+            extent,
+            num_array_layers,
+            mip_levels,
+            data: vec![],
+            regions: vec![],
+        }
+    }
+
+    fn format_info(&self) -> Result<FormatInfo, ContainerError> {
+        format_info(self.format).ok_or(ContainerError::UnknownFormat)
+    }
+
+    /// Appends the data of `layer_count` layers of the given mip level, returning the number of bytes consumed.
+    fn push_subresource(
+        &mut self,
+        mip_level: u32,
+        base_array_layer: u32,
+        layer_count: u32,
+        data: &[u8],
+    ) -> Result<usize, ContainerError> {
+        let format_info = self.format_info()?;
+        let image_extent = mip_extent(self.extent, mip_level);
+        let size = (format_info.image_size(image_extent) * layer_count as u64) as usize;
+        let data = data.get(..size).ok_or(ContainerError::InvalidData)?;
+
+        let buffer_offset =
+            get_aligned_offset(self.data.len() as u64, format_info.copy_alignment());
+        self.data.resize(buffer_offset as usize, 0);
+        self.data.extend_from_slice(data);
+        self.regions.push(vk::BufferImageCopy {
+            buffer_offset,
+            buffer_row_length: 0,
+            buffer_image_height: 0,
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level,
+                base_array_layer,
+                layer_count,
+            },
+            image_offset: vk::Offset3D::default(),
+            image_extent,
+        });
+        Ok(size)
+    }
+
+    /// Check whether the device supports images of this data's format with optimal tiling for `dst`,
+    /// i.e. with the format features of `ImageAccess::required_format_features`.
+    /// # Safety
+    /// Instance and physical device must be valid.
+    pub unsafe fn is_supported(
+        &self,
+        instance: &Instance,
+        physical_device: vk::PhysicalDevice,
+        dst: ImageAccess,
+    ) -> bool {
+        instance
+            .get_physical_device_format_properties(physical_device, self.format)
+            .optimal_tiling_features
+            .contains(dst.required_format_features())
+    }
+
+    /// Creates a texture with all mip levels and array layers, binds memory from `allocator` and
+    /// uploads the data with a single-use command buffer (using VulkanEngine::one_time_submit).
+    /// `dst` describes how the texture is used afterwards; the texture is created with the usage it requires.
+    /// Cube maps are created as cube-compatible textures and viewed as cubes (or cube arrays).
+    /// Returns `ContainerError::UnsupportedFormat` without creating any resources if the device
+    /// does not support the format for `dst`, see `is_supported`.
+    /// # Safety
+    /// Must be called on the thread able to submit command buffers to the given command pool.
+    pub unsafe fn upload(
+        &self,
+        instance: &Instance,
+        vk_engine: &VulkanEngine,
+        command_pool: vk::CommandPool,
+        allocator: &mut dyn Allocator,
+        dst: ImageAccess,
+    ) -> Result<LoadedTexture, ContainerError> {
+        if !self.is_supported(instance, vk_engine.physical_device, dst) {
+            return Err(ContainerError::UnsupportedFormat(self.format));
+        }
+
+        let texture = Texture::create(
+            vk_engine,
+            self.format,
+            self.image_type,
+            self.flags,
+            self.extent,
+            self.num_array_layers,
+            self.mip_levels,
+            dst.required_usage() | vk::ImageUsageFlags::TRANSFER_DST,
+        )?;
+        let view_type = texture.default_view_type();
+        LoadedTexture::bind_and_upload(vk_engine, allocator, texture, view_type, |texture| {
+            let mut staging_buffer = StagingBufferWithDedicatedAllocation::<u8>::allocate(
+                vk_engine,
+                self.data.len() as _,
+                vk::BufferUsageFlags::TRANSFER_SRC,
+                vk::SharingMode::EXCLUSIVE,
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            );
+            staging_buffer.buffer.write(&self.data, 0);
+            let copied = texture.copy_staging_regions_to_image(
+                vk_engine,
+                command_pool,
+                &staging_buffer.buffer,
+                &self.regions,
+                dst,
+            );
+            staging_buffer.destroy(&vk_engine.device);
+            copied.map_err(ContainerError::from)
+        })
+    }
+}
+
+/// Parses a KTX2 file. Supercompressed files and files without a Vulkan format (e.g. Basis Universal)
+/// are not supported.
+#[cfg(feature = "ktx2")]
+pub fn parse_ktx2(bytes: &[u8]) -> Result<TextureData, ContainerError> {
+    let reader = ktx2::Reader::new(bytes)?;
+    let header = reader.header();
+    if header.supercompression_scheme.is_some() {
+        return Err(ContainerError::Supercompressed);
+    }
+    let format = header
+        .format
+        .map(|format| vk::Format::from_raw(format.0.get() as i32))
+        .ok_or(ContainerError::UnknownFormat)?;
+
+    let image_type = if header.pixel_depth > 0 {
+        vk::ImageType::TYPE_3D
+    } else if header.pixel_height > 0 {
+        vk::ImageType::TYPE_2D
+    } else {
+        vk::ImageType::TYPE_1D
+    };
+    let extent = vk::Extent3D {
+        width: header.pixel_width,
+        height: header.pixel_height.max(1),
+        depth: header.pixel_depth.max(1),
+    };
+    // KTX2 stores all layers and faces of a level contiguously, in the order Vulkan expects.
+    let num_array_layers = header.layer_count.max(1) * header.face_count;
+    let flags = if header.face_count == 6 {
+        vk::ImageCreateFlags::CUBE_COMPATIBLE
+    } else {
+        vk::ImageCreateFlags::empty()
+    };
+    let mut texture_data = TextureData::new(
+        format,
+        image_type,
+        flags,
+        extent,
+        num_array_layers,
+        header.level_count.max(1),
+    );
+
+    for (mip_level, level) in reader.levels().enumerate() {
+        let size = texture_data.push_subresource(mip_level as u32, 0, num_array_layers, level)?;
+        if size != level.len() {
+            return Err(ContainerError::InvalidData);
+        }
+    }
+
+    Ok(texture_data)
+}
+
+#[cfg(feature = "dds")]
+fn dds_format(dds: &ddsfile::Dds) -> Option<vk::Format> {
+    use ddsfile::{D3DFormat, DxgiFormat};
+    use vk::Format as F;
+
+    if let Some(format) = dds.get_dxgi_format() {
+        let format = match format {
+            DxgiFormat::R8_UNorm => F::R8_UNORM,
+            DxgiFormat::R8G8_UNorm => F::R8G8_UNORM,
+            DxgiFormat::R8G8B8A8_Typeless | DxgiFormat::R8G8B8A8_UNorm => F::R8G8B8A8_UNORM,
+            DxgiFormat::R8G8B8A8_UNorm_sRGB => F::R8G8B8A8_SRGB,
+            DxgiFormat::B8G8R8A8_Typeless | DxgiFormat::B8G8R8A8_UNorm => F::B8G8R8A8_UNORM,
+            DxgiFormat::B8G8R8A8_UNorm_sRGB => F::B8G8R8A8_SRGB,
+            DxgiFormat::R16G16B16A16_Float => F::R16G16B16A16_SFLOAT,
+            DxgiFormat::R32G32B32A32_Float => F::R32G32B32A32_SFLOAT,
+            DxgiFormat::BC1_Typeless | DxgiFormat::BC1_UNorm => F::BC1_RGBA_UNORM_BLOCK,
+            DxgiFormat::BC1_UNorm_sRGB => F::BC1_RGBA_SRGB_BLOCK,
+            DxgiFormat::BC2_Typeless | DxgiFormat::BC2_UNorm => F::BC2_UNORM_BLOCK,
+            DxgiFormat::BC2_UNorm_sRGB => F::BC2_SRGB_BLOCK,
+            DxgiFormat::BC3_Typeless | DxgiFormat::BC3_UNorm => F::BC3_UNORM_BLOCK,
+            DxgiFormat::BC3_UNorm_sRGB => F::BC3_SRGB_BLOCK,
+            DxgiFormat::BC4_Typeless | DxgiFormat::BC4_UNorm => F::BC4_UNORM_BLOCK,
+            DxgiFormat::BC4_SNorm => F::BC4_SNORM_BLOCK,
+            DxgiFormat::BC5_Typeless | DxgiFormat::BC5_UNorm => F::BC5_UNORM_BLOCK,
+            DxgiFormat::BC5_SNorm => F::BC5_SNORM_BLOCK,
+            DxgiFormat::BC6H_Typeless | DxgiFormat::BC6H_UF16 => F::BC6H_UFLOAT_BLOCK,
+            DxgiFormat::BC6H_SF16 => F::BC6H_SFLOAT_BLOCK,
+            DxgiFormat::BC7_Typeless | DxgiFormat::BC7_UNorm => F::BC7_UNORM_BLOCK,
+            DxgiFormat::BC7_UNorm_sRGB => F::BC7_SRGB_BLOCK,
+            _ => return None,
+        };
+        return Some(format);
+    }
+
+    let format = match dds.get_d3d_format()? {
+        D3DFormat::DXT1 => F::BC1_RGBA_UNORM_BLOCK,
+        D3DFormat::DXT3 => F::BC2_UNORM_BLOCK,
+        D3DFormat::DXT5 => F::BC3_UNORM_BLOCK,
+        D3DFormat::A8R8G8B8 => F::B8G8R8A8_UNORM,
+        D3DFormat::A8B8G8R8 => F::R8G8B8A8_UNORM,
+        D3DFormat::L8 => F::R8_UNORM,
+        D3DFormat::A16B16G16R16F => F::R16G16B16A16_SFLOAT,
+        D3DFormat::A32B32G32R32F => F::R32G32B32A32_SFLOAT,
+        _ => return None,
+    };
+    Some(format)
+}
+
+/// Parses a DDS file. DXGI formats are mapped to their Vulkan equivalents; legacy DXT1, DXT3 and
+/// DXT5 files are loaded as BC1, BC2 and BC3, respectively.
+#[cfg(feature = "dds")]
+pub fn parse_dds(bytes: &[u8]) -> Result<TextureData, ContainerError> {
+    use ddsfile::{Caps2, D3D10ResourceDimension, MiscFlag};
+
+    let dds = ddsfile::Dds::read(bytes)?;
+    let format = dds_format(&dds).ok_or(ContainerError::UnknownFormat)?;
+
+    let (cube, array_size, is_3d) = match &dds.header10 {
+        Some(header10) => (
+            header10.misc_flag.contains(MiscFlag::TEXTURECUBE),
+            header10.array_size.max(1),
+            header10.resource_dimension == D3D10ResourceDimension::Texture3D,
+        ),
+        None => (
+            dds.header.caps2.contains(Caps2::CUBEMAP),
+            1,
+            dds.header.caps2.contains(Caps2::VOLUME),
+        ),
+    };
+    let num_array_layers = if cube { array_size * 6 } else { array_size };
+    let image_type = if is_3d {
+        vk::ImageType::TYPE_3D
+    } else {
+        vk::ImageType::TYPE_2D
+    };
+    let extent = vk::Extent3D {
+        width: dds.get_width(),
+        height: dds.get_height(),
+        depth: dds.get_depth(),
+    };
+    let mip_levels = dds.get_num_mipmap_levels().max(1);
+    let flags = if cube {
+        vk::ImageCreateFlags::CUBE_COMPATIBLE
+    } else {
+        vk::ImageCreateFlags::empty()
+    };
+    let mut texture_data = TextureData::new(
+        format,
+        image_type,
+        flags,
+        extent,
+        num_array_layers,
+        mip_levels,
+    );
+
+    // DDS stores the full mip chain of each layer (or cube face) one after the other.
+    let mut data = dds.data.as_slice();
+    for layer in 0..num_array_layers {
+        for mip_level in 0..mip_levels {
+            let size = texture_data.push_subresource(mip_level, layer, 1, data)?;
+            data = &data[size..];
+        }
+    }
+
+    Ok(texture_data)
+}
+
+#[cfg(all(test, any(feature = "ktx2", feature = "dds")))]
+mod tests {
+    use super::*;
+    #[cfg(feature = "dds")]
+    use ddsfile::{AlphaMode, D3D10ResourceDimension, Dds, DxgiFormat, NewDxgiParams};
+
+    /// Builds a KTX2 file of a 4x4 2D array with two layers and a full mip chain, where every byte of
+    /// mip level `n` is `n`. As the format requires, the smallest level is stored first.
+    #[cfg(feature = "ktx2")]
+    fn ktx2_fixture(format: vk::Format) -> Vec<u8> {
+        let level_sizes = [128u64, 32, 8];
+        let data_offsets = [200u64, 168, 160];
+        let header = [
+            format.as_raw() as u32, // vkFormat
+            1,                      // typeSize
+            4,                      // pixelWidth
+            4,                      // pixelHeight
+            0,                      // pixelDepth
+            2,                      // layerCount
+            1,                      // faceCount
+            3,                      // levelCount
+            0,                      // supercompressionScheme
+            152,                    // dfdByteOffset
+            4,                      // dfdByteLength
+            0,                      // kvdByteOffset
+            0,                      // kvdByteLength
+        ];
+
+        let mut bytes = vec![
+            0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+        ];
+        for value in header {
+            bytes.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        // sgdByteOffset and sgdByteLength
+        bytes.extend_from_slice(&[0; 16]);
+        for (&offset, &size) in data_offsets.iter().zip(&level_sizes) {
+            for value in [offset, size, size] {
+                bytes.extend_from_slice(&u64::to_le_bytes(value));
+            }
+        }
+        // An empty data format descriptor, consisting only of its total size.
+        bytes.extend_from_slice(&u32::to_le_bytes(4));
+        bytes.resize(160, 0);
+        for mip_level in (0..3).rev() {
+            bytes.resize(
+                bytes.len() + level_sizes[mip_level] as usize,
+                mip_level as u8,
+            );
+        }
+        bytes
+    }
+
+    #[cfg(feature = "ktx2")]
+    #[test]
+    fn ktx2_level_index_and_format() {
+        let texture_data = parse_ktx2(&ktx2_fixture(vk::Format::R8G8B8A8_UNORM)).unwrap();
+        assert_eq!(texture_data.format, vk::Format::R8G8B8A8_UNORM);
+        assert_eq!(texture_data.image_type, vk::ImageType::TYPE_2D);
+        assert_eq!(texture_data.flags, vk::ImageCreateFlags::empty());
+        assert_eq!(
+            texture_data.extent,
+            vk::Extent3D {
+                width: 4,
+                height: 4,
+                depth: 1
+            }
+        );
+        assert_eq!(texture_data.num_array_layers, 2);
+        assert_eq!(texture_data.mip_levels, 3);
+
+        // One region per level, covering both layers, in level order.
+        assert_eq!(texture_data.regions.len(), 3);
+        for (mip_level, region) in texture_data.regions.iter().enumerate() {
+            assert_eq!(region.image_subresource.mip_level, mip_level as u32);
+            assert_eq!(region.image_subresource.layer_count, 2);
+            assert_eq!(
+                texture_data.data[region.buffer_offset as usize],
+                mip_level as u8
+            );
+        }
+        let offsets = texture_data
+            .regions
+            .iter()
+            .map(|region| region.buffer_offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 128, 160]);
+        assert_eq!(texture_data.data.len(), 168);
+
+        assert!(matches!(
+            parse_ktx2(&ktx2_fixture(vk::Format::UNDEFINED)),
+            Err(ContainerError::UnknownFormat)
+        ));
+    }
+
+    #[cfg(feature = "dds")]
+    #[test]
+    fn dds_mip_chain_regions() {
+        let dds = Dds::new_dxgi(NewDxgiParams {
+            height: 8,
+            width: 8,
+            depth: None,
+            format: DxgiFormat::BC1_UNorm,
+            mipmap_levels: Some(4),
+            array_layers: Some(2),
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Unknown,
+        })
+        .unwrap();
+        let mut bytes = vec![];
+        dds.write(&mut bytes).unwrap();
+
+        let texture_data = parse_dds(&bytes).unwrap();
+        assert_eq!(texture_data.format, vk::Format::BC1_RGBA_UNORM_BLOCK);
+        assert_eq!(texture_data.num_array_layers, 2);
+        assert_eq!(texture_data.regions.len(), 8);
+        // 8x8, 4x4, 2x2 and 1x1 levels take 4, 1, 1 and 1 blocks of 8 bytes.
+        let offsets = texture_data
+            .regions
+            .iter()
+            .map(|region| region.buffer_offset)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, [0, 32, 40, 48, 56, 88, 96, 104]);
+        assert_eq!(texture_data.regions[3].image_extent.width, 1);
+    }
+}
@@ -0,0 +1,315 @@
+//! Converting pixel data on the host to a format the device supports, e.g. RGB8 to RGBA8,
+//! 16-bit grayscale to R16 or 32-bit floats to 16-bit floats.
+use crate::format::choose_supported_format;
+use ash::{vk, Instance};
+use half::f16;
+use thiserror::Error;
+
+/// How the colour values of pixel data are interpreted.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Colour data, e.g. albedo maps; sampling converts to linear values.
+    Srgb,
+    /// Non-colour data, e.g. normal maps or roughness maps; values are sampled as stored.
+    Linear,
+}
+
+impl ColorSpace {
+    /// Get the RGBA8 format matching the colour space.
+    pub fn rgba8_format(self) -> vk::Format {
+        match self {
+            ColorSpace::Srgb => vk::Format::R8G8B8A8_SRGB,
+            ColorSpace::Linear => vk::Format::R8G8B8A8_UNORM,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum ConvertError {
+    #[error("Pixel data cannot be converted to format {0:?}")]
+    UnsupportedDestination(vk::Format),
+    #[error("The pixel data is not a whole number of pixels")]
+    InvalidData,
+}
+
+/// The layout of source pixel data. Multi-byte channels are stored in native byte order.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    Gray8,
+    GrayAlpha8,
+    Rgb8,
+    Rgba8,
+    Gray16,
+    GrayAlpha16,
+    Rgb16,
+    Rgba16,
+    Rgb32F,
+    Rgba32F,
+}
+
+impl PixelFormat {
+    /// Get the number of channels per pixel.
+    pub fn channel_count(self) -> usize {
+        match self {
+            PixelFormat::Gray8 | PixelFormat::Gray16 => 1,
+            PixelFormat::GrayAlpha8 | PixelFormat::GrayAlpha16 => 2,
+            PixelFormat::Rgb8 | PixelFormat::Rgb16 | PixelFormat::Rgb32F => 3,
+            PixelFormat::Rgba8 | PixelFormat::Rgba16 | PixelFormat::Rgba32F => 4,
+        }
+    }
+
+    /// Get the size in bytes of a single channel.
+    pub fn channel_size(self) -> usize {
+        match self {
+            PixelFormat::Gray8
+            | PixelFormat::GrayAlpha8
+            | PixelFormat::Rgb8
+            | PixelFormat::Rgba8 => 1,
+            PixelFormat::Gray16
+            | PixelFormat::GrayAlpha16
+            | PixelFormat::Rgb16
+            | PixelFormat::Rgba16 => 2,
+            PixelFormat::Rgb32F | PixelFormat::Rgba32F => 4,
+        }
+    }
+
+    /// Get the size in bytes of a pixel.
+    pub fn pixel_size(self) -> usize {
+        self.channel_count() * self.channel_size()
+    }
+
+    /// Get the destination formats for data in this layout, in order of preference.
+    /// Formats that hold the data as it is come first, followed by wider formats.
+    /// Grayscale data without alpha prefers single-channel formats, so shaders read it from the red channel.
+    /// Floating-point data is linear, so `color_space` only affects integer data; there are no sRGB
+    /// formats with 16-bit channels, so 16-bit sRGB data is narrowed to 8 bits.
+    pub fn destination_candidates(self, color_space: ColorSpace) -> &'static [vk::Format] {
+        use vk::Format as F;
+        match (self, color_space) {
+            (PixelFormat::Gray8, ColorSpace::Srgb) => {
+                &[F::R8_SRGB, F::R8G8B8A8_SRGB, F::B8G8R8A8_SRGB]
+            }
+            (PixelFormat::Gray8, ColorSpace::Linear) => {
+                &[F::R8_UNORM, F::R8G8B8A8_UNORM, F::B8G8R8A8_UNORM]
+            }
+            (PixelFormat::Rgb8, ColorSpace::Srgb) => {
+                &[F::R8G8B8_SRGB, F::R8G8B8A8_SRGB, F::B8G8R8A8_SRGB]
+            }
+            (PixelFormat::Rgb8, ColorSpace::Linear) => {
+                &[F::R8G8B8_UNORM, F::R8G8B8A8_UNORM, F::B8G8R8A8_UNORM]
+            }
+            (PixelFormat::Gray16, ColorSpace::Linear) => &[
+                F::R16_UNORM,
+                F::R16_SFLOAT,
+                F::R16G16B16A16_UNORM,
+                F::R16G16B16A16_SFLOAT,
+                F::R8G8B8A8_UNORM,
+            ],
+            (
+                PixelFormat::GrayAlpha16 | PixelFormat::Rgb16 | PixelFormat::Rgba16,
+                ColorSpace::Linear,
+            ) => &[
+                F::R16G16B16A16_UNORM,
+                F::R16G16B16A16_SFLOAT,
+                F::R8G8B8A8_UNORM,
+            ],
+            (PixelFormat::Rgb32F | PixelFormat::Rgba32F, _) => {
+                &[F::R16G16B16A16_SFLOAT, F::R32G32B32A32_SFLOAT]
+            }
+            (_, ColorSpace::Srgb) => &[F::R8G8B8A8_SRGB, F::B8G8R8A8_SRGB],
+            (_, ColorSpace::Linear) => &[F::R8G8B8A8_UNORM, F::B8G8R8A8_UNORM],
+        }
+    }
+
+    /// Reads the pixel starting at `bytes` as normalized RGBA. Grayscale is replicated to RGB;
+    /// missing alpha is opaque.
+    fn read(self, bytes: &[u8]) -> [f32; 4] {
+        let channel = |index: usize| -> f32 {
+            match self.channel_size() {
+                1 => bytes[index] as f32 / u8::MAX as f32,
+                2 => {
+                    u16::from_ne_bytes([bytes[index * 2], bytes[index * 2 + 1]]) as f32
+                        / u16::MAX as f32
+                }
+                _ => {
+                    let start = index * 4;
+                    f32::from_ne_bytes([
+                        bytes[start],
+                        bytes[start + 1],
+                        bytes[start + 2],
+                        bytes[start + 3],
+                    ])
+                }
+            }
+        };
+        match self.channel_count() {
+            1 => [channel(0), channel(0), channel(0), 1.0],
+            2 => [channel(0), channel(0), channel(0), channel(1)],
+            3 => [channel(0), channel(1), channel(2), 1.0],
+            _ => [channel(0), channel(1), channel(2), channel(3)],
+        }
+    }
+}
+
+/// How a destination format stores a pixel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ChannelEncoding {
+    Unorm8,
+    Unorm16,
+    Float16,
+    Float32,
+}
+
+/// Get the channel encoding and the source channel of each destination channel of `format`,
+/// or `None` if conversions to `format` are not supported.
+fn destination_layout(format: vk::Format) -> Option<(ChannelEncoding, &'static [usize])> {
+    use vk::Format as F;
+    use ChannelEncoding::*;
+    let layout: (ChannelEncoding, &'static [usize]) = match format {
+        F::R8_UNORM | F::R8_SRGB => (Unorm8, &[0]),
+        F::R8G8B8_UNORM | F::R8G8B8_SRGB => (Unorm8, &[0, 1, 2]),
+        F::R8G8B8A8_UNORM | F::R8G8B8A8_SRGB => (Unorm8, &[0, 1, 2, 3]),
+        F::B8G8R8A8_UNORM | F::B8G8R8A8_SRGB => (Unorm8, &[2, 1, 0, 3]),
+        F::R16_UNORM => (Unorm16, &[0]),
+        F::R16G16B16A16_UNORM => (Unorm16, &[0, 1, 2, 3]),
+        F::R16_SFLOAT => (Float16, &[0]),
+        F::R16G16B16A16_SFLOAT => (Float16, &[0, 1, 2, 3]),
+        F::R32_SFLOAT => (Float32, &[0]),
+        F::R32G32B32A32_SFLOAT => (Float32, &[0, 1, 2, 3]),
+        _ => return None,
+    };
+    Some(layout)
+}
+
+/// Converts `data`, tightly packed pixels in the `source` layout, to tightly packed texels of `format`.
+/// Values are clamped to the range of normalized destination formats. If `premultiply_alpha` is set,
+/// the colour channels are multiplied by alpha; for sRGB formats this happens on the encoded values.
+pub fn convert_pixels(
+    data: &[u8],
+    source: PixelFormat,
+    format: vk::Format,
+    premultiply_alpha: bool,
+) -> Result<Vec<u8>, ConvertError> {
+    let (encoding, channels) =
+        destination_layout(format).ok_or(ConvertError::UnsupportedDestination(format))?;
+    if !data.len().is_multiple_of(source.pixel_size()) {
+        return Err(ConvertError::InvalidData);
+    }
+
+    let channel_size = match encoding {
+        ChannelEncoding::Unorm8 => 1,
+        ChannelEncoding::Unorm16 | ChannelEncoding::Float16 => 2,
+        ChannelEncoding::Float32 => 4,
+    };
+    let pixel_count = data.len() / source.pixel_size();
+    let mut converted = Vec::with_capacity(pixel_count * channels.len() * channel_size);
+    for pixel in data.chunks_exact(source.pixel_size()) {
+        let mut rgba = source.read(pixel);
+        if premultiply_alpha {
+            let alpha = rgba[3];
+            for channel in &mut rgba[..3] {
+                *channel *= alpha;
+            }
+        }
+        for &channel in channels {
+            let value = rgba[channel];
+            match encoding {
+                ChannelEncoding::Unorm8 => {
+                    converted.push((value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8)
+                }
+                ChannelEncoding::Unorm16 => {
+                    let value = (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16;
+                    converted.extend_from_slice(&value.to_ne_bytes());
+                }
+                ChannelEncoding::Float16 => {
+                    converted.extend_from_slice(&f16::from_f32(value).to_ne_bytes())
+                }
+                ChannelEncoding::Float32 => converted.extend_from_slice(&value.to_ne_bytes()),
+            }
+        }
+    }
+    Ok(converted)
+}
+
+/// Returns the first destination candidate of `source` (see `PixelFormat::destination_candidates`)
+/// that supports all `features` with optimal tiling on the device.
+/// # Safety
+/// Instance and physical device must be valid.
+pub unsafe fn choose_destination_format(
+    instance: &Instance,
+    physical_device: vk::PhysicalDevice,
+    source: PixelFormat,
+    color_space: ColorSpace,
+    features: vk::FormatFeatureFlags,
+) -> Option<vk::Format> {
+    choose_supported_format(
+        instance,
+        physical_device,
+        source.destination_candidates(color_space),
+        vk::ImageTiling::OPTIMAL,
+        features,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_and_premultiply() {
+        let rgb = [255, 0, 128, 10, 20, 30];
+        let converted = convert_pixels(&rgb, PixelFormat::Rgb8, vk::Format::R8G8B8A8_UNORM, false);
+        assert_eq!(converted.unwrap(), [255, 0, 128, 255, 10, 20, 30, 255]);
+        let converted = convert_pixels(&rgb, PixelFormat::Rgb8, vk::Format::B8G8R8A8_SRGB, false);
+        assert_eq!(converted.unwrap()[..4], [128, 0, 255, 255]);
+
+        let gray_alpha = [200, 128];
+        let converted = convert_pixels(
+            &gray_alpha,
+            PixelFormat::GrayAlpha8,
+            vk::Format::R8G8B8A8_UNORM,
+            true,
+        );
+        assert_eq!(converted.unwrap(), [100, 100, 100, 128]);
+
+        assert!(matches!(
+            convert_pixels(&rgb[..4], PixelFormat::Rgb8, vk::Format::R8_UNORM, false),
+            Err(ConvertError::InvalidData)
+        ));
+        assert!(matches!(
+            convert_pixels(
+                &rgb,
+                PixelFormat::Rgb8,
+                vk::Format::BC1_RGB_UNORM_BLOCK,
+                false
+            ),
+            Err(ConvertError::UnsupportedDestination(_))
+        ));
+    }
+
+    #[test]
+    fn widen_and_narrow() {
+        let gray16 = 0x8000u16.to_ne_bytes();
+        let converted = convert_pixels(&gray16, PixelFormat::Gray16, vk::Format::R16_UNORM, false);
+        assert_eq!(converted.unwrap(), 0x8000u16.to_ne_bytes());
+        let converted = convert_pixels(&gray16, PixelFormat::Gray16, vk::Format::R8_UNORM, false);
+        assert_eq!(converted.unwrap(), [128]);
+
+        let rgba32f = [0.5f32, -2.0, 65504.0, 1.0]
+            .iter()
+            .flat_map(|value| value.to_ne_bytes())
+            .collect::<Vec<_>>();
+        let converted = convert_pixels(
+            &rgba32f,
+            PixelFormat::Rgba32F,
+            vk::Format::R16G16B16A16_SFLOAT,
+            false,
+        )
+        .unwrap();
+        let halves = converted
+            .chunks_exact(2)
+            .map(|bytes| f16::from_ne_bytes([bytes[0], bytes[1]]).to_f32())
+            .collect::<Vec<_>>();
+        assert_eq!(halves, [0.5, -2.0, 65504.0, 1.0]);
+    }
+}
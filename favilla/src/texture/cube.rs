@@ -0,0 +1,187 @@
+//! Cube map faces and extraction of faces from cross layouts.
+use ash::vk;
+use thiserror::Error;
+
+/// A face of a cube map, in the order of the array layers Vulkan expects.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    /// All faces in layer order.
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    /// Get the array layer of this face in the cube with index `cube` of a cube map (array).
+    pub fn array_layer(self, cube: u32) -> u32 {
+        cube * 6 + self as u32
+    }
+}
+
+/// The arrangement of the six faces in a single cross-shaped image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CubeCross {
+    /// Four faces wide and three faces high:
+    /// ```text
+    ///     +Y
+    /// -X  +Z  +X  -Z
+    ///     -Y
+    /// ```
+    Horizontal,
+    /// Three faces wide and four faces high, with -Z rotated by 180 degrees:
+    /// ```text
+    ///     +Y
+    /// -X  +Z  +X
+    ///     -Y
+    ///     -Z
+    /// ```
+    Vertical,
+}
+
+#[derive(Error, Debug)]
+pub enum CubeError {
+    #[error("An image of {0}x{1} texels is not a horizontal or vertical cube cross")]
+    NotACross(u32, u32),
+    #[error("The image data does not match its extent")]
+    InvalidData,
+    #[error("The cube faces are not square or differ in size")]
+    FaceMismatch,
+}
+
+impl CubeCross {
+    /// Detect the cross layout from the image's extent.
+    pub fn detect(extent: vk::Extent2D) -> Result<Self, CubeError> {
+        let vk::Extent2D { width, height } = extent;
+        if width.is_multiple_of(4)
+            && height.is_multiple_of(3)
+            && width / 4 == height / 3
+            && width > 0
+        {
+            Ok(CubeCross::Horizontal)
+        } else if width.is_multiple_of(3)
+            && height.is_multiple_of(4)
+            && width / 3 == height / 4
+            && width > 0
+        {
+            Ok(CubeCross::Vertical)
+        } else {
+            Err(CubeError::NotACross(width, height))
+        }
+    }
+
+    /// Get the column and row of `face` in the cross, in units of faces.
+    fn position(self, face: CubeFace) -> (u32, u32) {
+        match (self, face) {
+            (_, CubeFace::PositiveX) => (2, 1),
+            (_, CubeFace::NegativeX) => (0, 1),
+            (_, CubeFace::PositiveY) => (1, 0),
+            (_, CubeFace::NegativeY) => (1, 2),
+            (_, CubeFace::PositiveZ) => (1, 1),
+            (CubeCross::Horizontal, CubeFace::NegativeZ) => (3, 1),
+            (CubeCross::Vertical, CubeFace::NegativeZ) => (1, 3),
+        }
+    }
+}
+
+/// Extracts the six faces from an image in a cross layout (see `CubeCross`) with `texel_size` bytes per texel.
+/// Returns the size of a face in texels and the faces, tightly packed in layer order (see `CubeFace`),
+/// ready to be uploaded to a texture created with `Texture::new_cube`.
+pub fn faces_from_cross(
+    data: &[u8],
+    extent: vk::Extent2D,
+    texel_size: usize,
+) -> Result<(u32, Vec<u8>), CubeError> {
+    let cross = CubeCross::detect(extent)?;
+    let row_size = extent.width as usize * texel_size;
+    if data.len() != row_size * extent.height as usize {
+        return Err(CubeError::InvalidData);
+    }
+
+    let face_size = match cross {
+        CubeCross::Horizontal => extent.width / 4,
+        CubeCross::Vertical => extent.width / 3,
+    } as usize;
+    let face_row_size = face_size * texel_size;
+    let mut faces = Vec::with_capacity(face_row_size * face_size * 6);
+    for face in CubeFace::ALL {
+        let (column, row) = cross.position(face);
+        let rotate = cross == CubeCross::Vertical && face == CubeFace::NegativeZ;
+        for y in 0..face_size {
+            let y = if rotate { face_size - 1 - y } else { y };
+            let start = (row as usize * face_size + y) * row_size + column as usize * face_row_size;
+            let face_row = &data[start..start + face_row_size];
+            if rotate {
+                faces.extend(face_row.chunks_exact(texel_size).rev().flatten());
+            } else {
+                faces.extend_from_slice(face_row);
+            }
+        }
+    }
+    Ok((face_size as u32, faces))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cross_faces() {
+        // Each texel holds the index of the face it belongs to, or 0xff outside of the cross.
+        let horizontal = [[0xff, 2, 0xff, 0xff], [1, 4, 0, 5], [0xff, 3, 0xff, 0xff]];
+        let data = horizontal.iter().flatten().copied().collect::<Vec<u8>>();
+        let extent = vk::Extent2D {
+            width: 4,
+            height: 3,
+        };
+        let (size, faces) = faces_from_cross(&data, extent, 1).unwrap();
+        assert_eq!(size, 1);
+        assert_eq!(faces, [0, 1, 2, 3, 4, 5]);
+
+        // A 2x2 vertical cross with distinct texels in the rotated -Z face.
+        let mut data = vec![0u8; 6 * 8];
+        for (face, (column, row)) in [
+            (0, (2, 1)),
+            (1, (0, 1)),
+            (2, (1, 0)),
+            (3, (1, 2)),
+            (4, (1, 1)),
+        ] {
+            for y in 0..2 {
+                for x in 0..2 {
+                    data[(row * 2 + y) * 6 + column * 2 + x] = face;
+                }
+            }
+        }
+        data[6 * 6 + 2..6 * 6 + 4].copy_from_slice(&[10, 11]);
+        data[7 * 6 + 2..7 * 6 + 4].copy_from_slice(&[12, 13]);
+        let extent = vk::Extent2D {
+            width: 6,
+            height: 8,
+        };
+        let (size, faces) = faces_from_cross(&data, extent, 1).unwrap();
+        assert_eq!(size, 2);
+        assert_eq!(&faces[..4], [0; 4]);
+        assert_eq!(&faces[16..20], [4; 4]);
+        assert_eq!(&faces[20..], [13, 12, 11, 10]);
+
+        assert!(matches!(
+            CubeCross::detect(vk::Extent2D {
+                width: 4,
+                height: 4
+            }),
+            Err(CubeError::NotACross(4, 4))
+        ));
+    }
+}
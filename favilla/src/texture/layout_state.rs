@@ -0,0 +1,318 @@
+use super::{transition_layout, ImageAccess, Texture, TextureRange};
+use ash::vk::ImageLayout;
+use ash::{vk, Device};
+
+const WRITE_ACCESS: vk::AccessFlags = vk::AccessFlags::from_raw(
+    vk::AccessFlags::SHADER_WRITE.as_raw()
+        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()
+        | vk::AccessFlags::TRANSFER_WRITE.as_raw()
+        | vk::AccessFlags::HOST_WRITE.as_raw()
+        | vk::AccessFlags::MEMORY_WRITE.as_raw(),
+);
+
+/// The tracked layout and last access of every mip level and array layer of a texture.
+/// Used to derive the source of layout transitions, see `Texture::transition_to`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutState {
+    mip_levels: u32,
+    states: Vec<SubresourceState>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct SubresourceState {
+    access: ImageAccess,
+    /// The source scope of the last barrier, i.e. the last write or layout transition.
+    barrier_src_stages: vk::PipelineStageFlags,
+    barrier_src_access: vk::AccessFlags,
+    /// The destination scope of all barriers since; reads outside of it have to wait for the last barrier's source.
+    visible_stages: vk::PipelineStageFlags,
+    visible_access: vk::AccessFlags,
+}
+
+impl SubresourceState {
+    fn new(access: ImageAccess) -> Self {
+        Self {
+            access,
+            barrier_src_stages: vk::PipelineStageFlags::empty(),
+            barrier_src_access: vk::AccessFlags::empty(),
+            visible_stages: access.stage_mask,
+            visible_access: access.access_mask,
+        }
+    }
+
+    fn after_barrier(src: ImageAccess, dst: ImageAccess) -> Self {
+        Self {
+            access: dst,
+            barrier_src_stages: src.stage_mask,
+            barrier_src_access: src.access_mask & WRITE_ACCESS,
+            visible_stages: dst.stage_mask,
+            visible_access: dst.access_mask,
+        }
+    }
+}
+
+impl LayoutState {
+    /// Create a new state with all mip levels and layers in `UNDEFINED` layout.
+    pub fn new(mip_levels: u32, num_array_layers: u32) -> Self {
+        Self {
+            mip_levels,
+            states: vec![
+                SubresourceState::new(ImageAccess::UNDEFINED);
+                (mip_levels * num_array_layers) as usize
+            ],
+        }
+    }
+
+    fn index(&self, mip_level: u32, array_layer: u32) -> usize {
+        (array_layer * self.mip_levels + mip_level) as usize
+    }
+
+    /// Get the tracked access of a single mip level and array layer.
+    pub fn get(&self, mip_level: u32, array_layer: u32) -> ImageAccess {
+        self.states[self.index(mip_level, array_layer)].access
+    }
+
+    /// Overwrite the tracked access of `range`, e.g. after transitioning it manually.
+    /// The access is assumed to be synchronized with all previous accesses.
+    pub fn set(&mut self, range: TextureRange, access: ImageAccess) {
+        self.set_state(range, SubresourceState::new(access));
+    }
+
+    fn set_state(&mut self, range: TextureRange, state: SubresourceState) {
+        for mip_level in range.base_mip_level..range.base_mip_level + range.level_count {
+            for array_layer in range.base_array_layer..range.base_array_layer + range.layer_count {
+                let index = self.index(mip_level, array_layer);
+                self.states[index] = state;
+            }
+        }
+    }
+
+    /// Mark the content of `range` as no longer needed. The next transition still waits for the
+    /// last access, but starts from `UNDEFINED` layout, which allows the device to discard the content.
+    pub fn discard(&mut self, range: TextureRange) {
+        for mip_level in range.base_mip_level..range.base_mip_level + range.level_count {
+            for array_layer in range.base_array_layer..range.base_array_layer + range.layer_count {
+                let index = self.index(mip_level, array_layer);
+                self.states[index].access.layout = ImageLayout::UNDEFINED;
+            }
+        }
+    }
+
+    /// Updates the tracked state of `range` to `dst` and returns the sub-ranges that need a barrier,
+    /// together with their previous access. Sub-ranges with the same previous access are merged.
+    /// Reads following reads in the same layout need no barrier if the barrier that made the last write visible
+    /// already covered their stages and accesses. Otherwise, they get a barrier waiting for that write, returned
+    /// as the previous access. The stages and accesses of reads are accumulated, so a later write waits for all of them.
+    pub fn transition(
+        &mut self,
+        range: TextureRange,
+        dst: ImageAccess,
+    ) -> Vec<(TextureRange, ImageAccess)> {
+        let mut transitions: Vec<(TextureRange, ImageAccess)> = Vec::new();
+        for mip_level in range.base_mip_level..range.base_mip_level + range.level_count {
+            let mut array_layer = range.base_array_layer;
+            while array_layer < range.base_array_layer + range.layer_count {
+                let state = self.states[self.index(mip_level, array_layer)];
+                let mut src = state.access;
+                let mut layer_count = 1;
+                while array_layer + layer_count < range.base_array_layer + range.layer_count
+                    && self.states[self.index(mip_level, array_layer + layer_count)] == state
+                {
+                    layer_count += 1;
+                }
+                let run = TextureRange {
+                    base_mip_level: mip_level,
+                    level_count: 1,
+                    base_array_layer: array_layer,
+                    layer_count,
+                };
+                array_layer += layer_count;
+
+                if src.layout == dst.layout
+                    && src.layout != ImageLayout::UNDEFINED
+                    && !src.access_mask.intersects(WRITE_ACCESS)
+                    && !dst.access_mask.intersects(WRITE_ACCESS)
+                {
+                    let covered = state.visible_stages.contains(dst.stage_mask)
+                        && state.visible_access.contains(dst.access_mask);
+                    self.set_state(
+                        run,
+                        SubresourceState {
+                            access: ImageAccess {
+                                stage_mask: src.stage_mask | dst.stage_mask,
+                                access_mask: src.access_mask | dst.access_mask,
+                                layout: dst.layout,
+                            },
+                            visible_stages: state.visible_stages | dst.stage_mask,
+                            visible_access: state.visible_access | dst.access_mask,
+                            ..state
+                        },
+                    );
+                    if covered {
+                        continue;
+                    }
+                    // Wait for the last write again; including the stages of the last barrier's
+                    // destination chains this barrier to it, which also covers its layout transition.
+                    src = ImageAccess {
+                        stage_mask: state.barrier_src_stages | state.visible_stages,
+                        access_mask: state.barrier_src_access,
+                        layout: dst.layout,
+                    };
+                } else {
+                    self.set_state(run, SubresourceState::after_barrier(src, dst));
+                }
+
+                // Merge with the same layers of the previous mip level.
+                match transitions.iter_mut().find(|(range, access)| {
+                    *access == src
+                        && range.base_array_layer == run.base_array_layer
+                        && range.layer_count == run.layer_count
+                        && range.base_mip_level + range.level_count == mip_level
+                }) {
+                    Some((range, _)) => range.level_count += 1,
+                    None => transitions.push((run, src)),
+                }
+            }
+        }
+        transitions
+    }
+}
+
+/// Collects layout transitions of many textures and records them with a single pipeline barrier.
+#[derive(Debug, Default)]
+pub struct LayoutTransitions {
+    pub barriers: Vec<vk::ImageMemoryBarrier>,
+    pub src_stage_mask: vk::PipelineStageFlags,
+    pub dst_stage_mask: vk::PipelineStageFlags,
+}
+
+impl LayoutTransitions {
+    /// Create a new, empty set of transitions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// True iff no barriers have been added.
+    pub fn is_empty(&self) -> bool {
+        self.barriers.is_empty()
+    }
+
+    /// Add the barriers transitioning `range` of `texture` from its tracked state to `dst`,
+    /// and update the tracked state.
+    pub fn add(&mut self, texture: &mut Texture, range: TextureRange, dst: ImageAccess) {
+        for (range, src) in texture.layout_state.transition(range, dst) {
+            self.barriers.push(texture.get_aspect_image_memory_barrier(
+                texture.aspect_mask(),
+                range,
+                src.access_mask,
+                dst.access_mask,
+                src.layout,
+                dst.layout,
+            ));
+            self.src_stage_mask |= src.stage_mask;
+            self.dst_stage_mask |= dst.stage_mask;
+        }
+    }
+
+    /// Add all barriers of `other`.
+    pub fn merge(&mut self, other: &LayoutTransitions) {
+        self.barriers.extend_from_slice(&other.barriers);
+        self.src_stage_mask |= other.src_stage_mask;
+        self.dst_stage_mask |= other.dst_stage_mask;
+    }
+
+    /// Records all collected barriers with a single `transition_layout` call.
+    /// Nothing is recorded if no barriers have been added.
+    /// # Safety
+    /// `command_buffer` must be in the recording state. The tracked states of the textures must
+    /// match the state of the textures at the point of recording.
+    pub unsafe fn record(&self, device: &Device, command_buffer: vk::CommandBuffer) {
+        if self.is_empty() {
+            return;
+        }
+        transition_layout(
+            device,
+            command_buffer,
+            &self.barriers,
+            self.src_stage_mask,
+            self.dst_stage_mask,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merged_transitions() {
+        let mut state = LayoutState::new(3, 4);
+        let full = TextureRange {
+            base_mip_level: 0,
+            level_count: 3,
+            base_array_layer: 0,
+            layer_count: 4,
+        };
+        let transitions = state.transition(full, ImageAccess::TRANSFER_DST);
+        assert_eq!(transitions, vec![(full, ImageAccess::UNDEFINED)]);
+
+        let layer = TextureRange {
+            base_array_layer: 2,
+            layer_count: 1,
+            ..full
+        };
+        state.transition(layer, ImageAccess::FRAGMENT_SHADER_READ);
+        let transitions = state.transition(full, ImageAccess::FRAGMENT_SHADER_READ);
+        assert_eq!(
+            transitions,
+            vec![
+                (
+                    TextureRange {
+                        layer_count: 2,
+                        ..full
+                    },
+                    ImageAccess::TRANSFER_DST
+                ),
+                (
+                    TextureRange {
+                        base_array_layer: 3,
+                        layer_count: 1,
+                        ..full
+                    },
+                    ImageAccess::TRANSFER_DST
+                ),
+            ]
+        );
+
+        // Reads in the same layout need no barrier if the transfer write was made visible to them...
+        assert!(state
+            .transition(full, ImageAccess::FRAGMENT_SHADER_READ)
+            .is_empty());
+
+        // ...but reads in other stages still have to wait for it.
+        let wait_for_write = ImageAccess {
+            stage_mask: vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        };
+        assert_eq!(
+            state.transition(full, ImageAccess::VERTEX_SHADER_READ),
+            vec![(full, wait_for_write)]
+        );
+        assert!(state
+            .transition(full, ImageAccess::VERTEX_SHADER_READ)
+            .is_empty());
+        assert_eq!(
+            state.get(1, 2).stage_mask,
+            vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::VERTEX_SHADER
+        );
+
+        // A write waits for all reads.
+        let reads = state.get(0, 0);
+        assert_eq!(
+            state.transition(full, ImageAccess::TRANSFER_DST),
+            vec![(full, reads)]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Loading textures from image files. Requires the `image` feature.
+//!
+//! PNG, JPEG and TGA files are decoded to RGBA8, uploaded through a staging buffer
+//! and returned as a texture ready for the requested use.
+//! `load_converted_from_memory` keeps the decoded pixel layout instead and converts it to a format
+//! the device supports (see `convert`).
+//! Cube maps can be loaded from six face images or a single image in a cross layout.
+use crate::allocator::{Allocator, SubAllocationError};
+pub use crate::texture::convert::ColorSpace;
+use crate::texture::convert::{
+    choose_destination_format, convert_pixels, ConvertError, PixelFormat,
+};
+use crate::texture::cube::{faces_from_cross, CubeError};
+use crate::texture::{ImageAccess, LoadedTexture, Texture, TextureCopyError};
+use crate::upload_batch::UploadBatch;
+use crate::vk_engine::VulkanEngine;
+use ash::{vk, Instance};
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TextureLoadError {
+    #[error("Failed to read image file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to decode image: {0}")]
+    Decode(#[from] image::ImageError),
+    #[error("Invalid cube map: {0}")]
+    Cube(#[from] CubeError),
+    #[error("Invalid texture data: {0}")]
+    Copy(#[from] TextureCopyError),
+    #[error("Failed to convert pixel data: {0}")]
+    Convert(#[from] ConvertError),
+    #[error("The device supports none of the formats the image can be converted to")]
+    NoSupportedFormat,
+    #[error("Failed to allocate texture memory: {0}")]
+    Allocation(#[from] SubAllocationError),
+    #[error("Vulkan error: {0}")]
+    Vulkan(#[from] vk::Result),
+}
+
+/// Loads the image file at `path`; see `load_from_memory`.
+/// # Safety
+/// See `load_from_memory`.
+pub unsafe fn load_from_file<P: AsRef<Path>>(
+    vk_engine: &VulkanEngine,
+    command_pool: vk::CommandPool,
+    allocator: &mut dyn Allocator,
+    path: P,
+    color_space: ColorSpace,
+    dst: ImageAccess,
+) -> Result<LoadedTexture, TextureLoadError> {
+    let bytes = std::fs::read(path)?;
+    load_from_memory(vk_engine, command_pool, allocator, &bytes, color_space, dst)
+}
+
+/// Decodes an encoded PNG, JPEG or TGA image to RGBA8 and uploads it to a new 2D texture,
+/// using `color_space` to choose between the SRGB and UNORM format.
+/// The texture memory is taken from `allocator`, which has to hand out device-local memory.
+/// The upload is submitted with a single-use command buffer (using VulkanEngine::one_time_submit).
+/// `dst` describes how the texture is used afterwards, e.g. `ImageAccess::FRAGMENT_SHADER_READ`;
+/// the texture is created with the usage it requires.
+/// # Safety
+/// Must be called on the thread able to submit command buffers to the given command pool.
+pub unsafe fn load_from_memory(
+    vk_engine: &VulkanEngine,
+    command_pool: vk::CommandPool,
+    allocator: &mut dyn Allocator,
+    bytes: &[u8],
+    color_space: ColorSpace,
+    dst: ImageAccess,
+) -> Result<LoadedTexture, TextureLoadError> {
+    let image = image::load_from_memory(bytes)?.into_rgba8();
+    let format = color_space.rgba8_format();
+    let extent = vk::Extent3D {
+        width: image.width(),
+        height: image.height(),
+        depth: 1,
+    };
+
+    let texture = Texture::new(
+        vk_engine,
+        format,
+        vk::ImageType::TYPE_2D,
+        extent,
+        1,
+        dst.required_usage(),
+    )?;
+    upload_data(
+        vk_engine,
+        command_pool,
+        allocator,
+        texture,
+        image.as_raw(),
+        dst,
+    )
+}
+
+/// Decodes an encoded PNG, JPEG or TGA image, converts it to the first format the device supports
+/// for `dst` and uploads it to a new 2D texture. Sampled textures also require linear filtering.
+/// Unlike `load_from_memory`, the decoded pixel layout is kept where possible, e.g. grayscale images
+/// become single-channel textures and 16-bit images keep their precision with `ColorSpace::Linear`;
+/// see `PixelFormat::destination_candidates` for the formats tried.
+/// If `premultiply_alpha` is set, the colour channels are multiplied by alpha before uploading.
+/// # Safety
+/// See `load_from_memory`. Instance and the engine's physical device must be valid.
+#[allow(clippy::too_many_arguments)]
+pub unsafe fn load_converted_from_memory(
+    vk_engine: &VulkanEngine,
+    instance: &Instance,
+    command_pool: vk::CommandPool,
+    allocator: &mut dyn Allocator,
+    bytes: &[u8],
+    color_space: ColorSpace,
+    premultiply_alpha: bool,
+    dst: ImageAccess,
+) -> Result<LoadedTexture, TextureLoadError> {
+    let image = image::load_from_memory(bytes)?;
+    let extent = vk::Extent3D {
+        width: image.width(),
+        height: image.height(),
+        depth: 1,
+    };
+    let (source, data) = pixel_data(image);
+
+    let mut features = dst.required_format_features();
+    if features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE) {
+        features |= vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR;
+    }
+    let format = choose_destination_format(
+        instance,
+        vk_engine.physical_device,
+        source,
+        color_space,
+        features,
+    )
+    .ok_or(TextureLoadError::NoSupportedFormat)?;
+    let data = convert_pixels(&data, source, format, premultiply_alpha)?;
+
+    let texture = Texture::new(
+        vk_engine,
+        format,
+        vk::ImageType::TYPE_2D,
+        extent,
+        1,
+        dst.required_usage(),
+    )?;
+    upload_data(vk_engine, command_pool, allocator, texture, &data, dst)
+}
+
+/// Get the pixel layout and the raw pixel data of a decoded image.
+/// Layouts without a matching `PixelFormat` are converted to RGBA8.
+fn pixel_data(image: image::DynamicImage) -> (PixelFormat, Vec<u8>) {
+    use image::ColorType;
+    let source = match image.color() {
+        ColorType::L8 => PixelFormat::Gray8,
+        ColorType::La8 => PixelFormat::GrayAlpha8,
+        ColorType::Rgb8 => PixelFormat::Rgb8,
+        ColorType::Rgba8 => PixelFormat::Rgba8,
+        ColorType::L16 => PixelFormat::Gray16,
+        ColorType::La16 => PixelFormat::GrayAlpha16,
+        ColorType::Rgb16 => PixelFormat::Rgb16,
+        ColorType::Rgba16 => PixelFormat::Rgba16,
+        ColorType::Rgb32F => PixelFormat::Rgb32F,
+        ColorType::Rgba32F => PixelFormat::Rgba32F,
+        _ => return (PixelFormat::Rgba8, image.into_rgba8().into_raw()),
+    };
+    (source, image.into_bytes())
+}
+
+/// Decodes six encoded face images (see `load_from_memory`) and uploads them to a new cube map texture,
+/// viewed as a cube. The faces are given in layer order: +X, -X, +Y, -Y, +Z, -Z.
+/// All faces must be square and of the same size.
+/// # Safety
+/// See `load_from_memory`.
+pub unsafe fn load_cube_from_memory(
+    vk_engine: &VulkanEngine,
+    command_pool: vk::CommandPool,
+    allocator: &mut dyn Allocator,
+    faces: [&[u8]; 6],
+    color_space: ColorSpace,
+    dst: ImageAccess,
+) -> Result<LoadedTexture, TextureLoadError> {
+    let mut data = vec![];
+    let mut size = None;
+    for face in faces {
+        let image = image::load_from_memory(face)?.into_rgba8();
+        if image.width() != image.height() || *size.get_or_insert(image.width()) != image.width() {
+            return Err(CubeError::FaceMismatch.into());
+        }
+        data.extend_from_slice(image.as_raw());
+    }
+
+    let texture = Texture::new_cube(
+        vk_engine,
+        color_space.rgba8_format(),
+        size.unwrap_or_default(),
+        1,
+        dst.required_usage(),
+    )?;
+    upload_data(vk_engine, command_pool, allocator, texture, &data, dst)
+}
+
+/// Decodes an encoded image holding all six faces in a horizontal or vertical cross layout
+/// (see `cube::CubeCross`) and uploads it to a new cube map texture, viewed as a cube.
+/// # Safety
+/// See `load_from_memory`.
+pub unsafe fn load_cube_cross_from_memory(
+    vk_engine: &VulkanEngine,
+    command_pool: vk::CommandPool,
+    allocator: &mut dyn Allocator,
+    bytes: &[u8],
+    color_space: ColorSpace,
+    dst: ImageAccess,
+) -> Result<LoadedTexture, TextureLoadError> {
+    let image = image::load_from_memory(bytes)?.into_rgba8();
+    let extent = vk::Extent2D {
+        width: image.width(),
+        height: image.height(),
+    };
+    let (size, data) = faces_from_cross(image.as_raw(), extent, 4)?;
+
+    let texture = Texture::new_cube(
+        vk_engine,
+        color_space.rgba8_format(),
+        size,
+        1,
+        dst.required_usage(),
+    )?;
+    upload_data(vk_engine, command_pool, allocator, texture, &data, dst)
+}
+
+/// Binds memory from `allocator` to `texture`, uploads `data` to all of its layers and creates
+/// a view with the texture's default view type. Destroys the texture and frees its memory on failure.
+unsafe fn upload_data(
+    vk_engine: &VulkanEngine,
+    command_pool: vk::CommandPool,
+    allocator: &mut dyn Allocator,
+    texture: Texture,
+    data: &[u8],
+    dst: ImageAccess,
+) -> Result<LoadedTexture, TextureLoadError> {
+    let view_type = texture.default_view_type();
+    LoadedTexture::bind_and_upload(vk_engine, allocator, texture, view_type, |texture| {
+        let mut upload_batch = UploadBatch::new();
+        upload_batch.upload_texture(data, texture, dst)?;
+        upload_batch.submit(vk_engine, command_pool);
+        Ok(())
+    })
+}
@@ -0,0 +1,194 @@
+//! Host-side mip chain generation, used for formats that cannot be blitted on the device.
+use ash::vk;
+
+use super::MipmapError;
+
+/// Get the number of mip levels of a full mip chain for the given extent,
+/// i.e. `floor(log2(max(width, height, depth))) + 1`.
+pub fn mip_level_count(extent: vk::Extent3D) -> u32 {
+    let max = extent.width.max(extent.height).max(extent.depth).max(1);
+    32 - max.leading_zeros()
+}
+
+/// Get the extent of the given mip level of an image with the given base extent.
+pub fn mip_extent(extent: vk::Extent3D, mip_level: u32) -> vk::Extent3D {
+    vk::Extent3D {
+        width: (extent.width >> mip_level).max(1),
+        height: (extent.height >> mip_level).max(1),
+        depth: (extent.depth >> mip_level).max(1),
+    }
+}
+
+/// Get the number of 8 bit channels per texel for formats that can be downsampled on the host.
+/// Channels are averaged independently, so sRGB formats are filtered in gamma space.
+pub fn u8_channel_count(format: vk::Format) -> Option<usize> {
+    match format {
+        vk::Format::R8_UNORM | vk::Format::R8_SRGB => Some(1),
+        vk::Format::R8G8_UNORM | vk::Format::R8G8_SRGB => Some(2),
+        vk::Format::R8G8B8_UNORM
+        | vk::Format::R8G8B8_SRGB
+        | vk::Format::B8G8R8_UNORM
+        | vk::Format::B8G8R8_SRGB => Some(3),
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB => Some(4),
+        _ => None,
+    }
+}
+
+/// A single level of a `MipChain`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MipLevel {
+    /// Offset in bytes of the level's data within `MipChain::data`.
+    pub offset: usize,
+    pub extent: vk::Extent3D,
+}
+
+/// A mip chain generated on the host. Every level holds all array layers, tightly packed layer after layer.
+#[derive(Debug)]
+pub struct MipChain {
+    pub data: Vec<u8>,
+    pub levels: Vec<MipLevel>,
+}
+
+impl MipChain {
+    /// Generates `mip_levels` levels from `data` with a box filter.
+    /// `data` holds all array layers of the base level, tightly packed, with `channels` bytes per texel,
+    /// and must be exactly that size.
+    pub fn generate(
+        data: &[u8],
+        extent: vk::Extent3D,
+        num_array_layers: u32,
+        channels: usize,
+        mip_levels: u32,
+    ) -> Result<Self, MipmapError> {
+        let texel_count = |extent: vk::Extent3D| {
+            extent.width as usize * extent.height as usize * extent.depth as usize
+        };
+        let base_size = texel_count(extent) * channels * num_array_layers as usize;
+        if data.len() != base_size {
+            return Err(MipmapError::DataSizeMismatch(data.len(), base_size));
+        }
+
+        let mut chain = Self {
+            data: data.to_vec(),
+            levels: vec![MipLevel { offset: 0, extent }],
+        };
+
+        for mip_level in 1..mip_levels {
+            let src = *chain.levels.last().unwrap();
+            let dst_extent = mip_extent(extent, mip_level);
+            let src_layer_size = texel_count(src.extent) * channels;
+            let dst_offset = chain.data.len();
+
+            for layer in 0..num_array_layers as usize {
+                let src_start = src.offset + layer * src_layer_size;
+                let src_layer = chain.data[src_start..src_start + src_layer_size].to_vec();
+                downsample(
+                    &src_layer,
+                    src.extent,
+                    dst_extent,
+                    channels,
+                    &mut chain.data,
+                );
+            }
+
+            chain.levels.push(MipLevel {
+                offset: dst_offset,
+                extent: dst_extent,
+            });
+        }
+
+        Ok(chain)
+    }
+}
+
+/// Appends the box-filtered `src` layer with extent `src_extent` to `dst`.
+fn downsample(
+    src: &[u8],
+    src_extent: vk::Extent3D,
+    dst_extent: vk::Extent3D,
+    channels: usize,
+    dst: &mut Vec<u8>,
+) {
+    // Odd extents sample the last texel twice instead of covering three texels.
+    let sources = |dst_coordinate: u32, src_size: u32| {
+        let first = (dst_coordinate * 2).min(src_size - 1);
+        [first, (first + 1).min(src_size - 1)]
+    };
+    let index = |x: u32, y: u32, z: u32| {
+        ((z as usize * src_extent.height as usize + y as usize) * src_extent.width as usize
+            + x as usize)
+            * channels
+    };
+
+    for z in 0..dst_extent.depth {
+        let src_zs = sources(z, src_extent.depth);
+        for y in 0..dst_extent.height {
+            let src_ys = sources(y, src_extent.height);
+            for x in 0..dst_extent.width {
+                let src_xs = sources(x, src_extent.width);
+                for channel in 0..channels {
+                    let mut sum = 0u32;
+                    for &src_z in &src_zs {
+                        for &src_y in &src_ys {
+                            for &src_x in &src_xs {
+                                sum += src[index(src_x, src_y, src_z) + channel] as u32;
+                            }
+                        }
+                    }
+                    dst.push(((sum + 4) / 8) as u8);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extent(width: u32, height: u32) -> vk::Extent3D {
+        vk::Extent3D {
+            width,
+            height,
+            depth: 1,
+        }
+    }
+
+    #[test]
+    fn full_chain_level_count() {
+        assert_eq!(mip_level_count(extent(1, 1)), 1);
+        assert_eq!(mip_level_count(extent(256, 128)), 9);
+        assert_eq!(mip_level_count(extent(5, 3)), 3);
+        assert_eq!(mip_extent(extent(5, 3), 2), extent(1, 1));
+    }
+
+    #[test]
+    fn box_filter_chain() {
+        let data = [
+            0, 10, 255, 255, //
+            20, 30, 255, 255, //
+            40, 50, 255, 255, //
+            60, 70, 255, 255,
+        ];
+        let chain = MipChain::generate(&data, extent(2, 2), 1, 4, 2).unwrap();
+        assert_eq!(
+            chain.levels[1],
+            MipLevel {
+                offset: 16,
+                extent: extent(1, 1),
+            }
+        );
+        assert_eq!(&chain.data[16..], &[30, 40, 255, 255]);
+    }
+
+    #[test]
+    fn data_size_mismatch() {
+        assert!(matches!(
+            MipChain::generate(&[0; 20], extent(2, 2), 1, 4, 2),
+            Err(MipmapError::DataSizeMismatch(20, 16))
+        ));
+    }
+}
@@ -0,0 +1,500 @@
+use crate::cleanup_queue::CleanupQueue;
+use crate::memory::find_memory_type_index;
+use crate::texture::layout_state::LayoutTransitions;
+use crate::texture::mipmap::u8_channel_count;
+use crate::texture::{ImageAccess, Texture, TextureCopyError, TextureRange, TextureRegion};
+use crate::upload_batch::UploadBatch;
+use crate::vk_engine::VulkanEngine;
+use ash::vk::ImageLayout;
+use ash::{vk, Device};
+use cgmath::Vector2;
+use thiserror::Error;
+
+/// Bytes per texel of the RGBA images packed into an atlas.
+const TEXEL_SIZE: usize = 4;
+
+#[derive(Error, Debug)]
+pub enum AtlasError {
+    #[error("An image of {0}x{1} texels (including padding) does not fit into an atlas page")]
+    TooLarge(u32, u32),
+    #[error("The image is empty or its data does not match its extent")]
+    InvalidData,
+    #[error("Format {0:?} does not have four 8 bit channels")]
+    UnsupportedFormat(vk::Format),
+    #[error("Invalid upload: {0}")]
+    Copy(#[from] TextureCopyError),
+    #[error("Vulkan error: {0}")]
+    Vulkan(#[from] vk::Result),
+}
+
+/// A skyline rectangle packer using the bottom-left heuristic.
+/// The skyline is the upper edge of the packed area; rectangles are placed on top of it
+/// wherever their top edge ends up lowest.
+#[derive(Debug, Clone)]
+pub struct SkylinePacker {
+    pub extent: vk::Extent2D,
+    /// Segments of the skyline as (x, y, width), ordered by x and covering the full width.
+    skyline: Vec<(u32, u32, u32)>,
+}
+
+impl SkylinePacker {
+    /// Create a new, empty packer for the given area.
+    pub fn new(extent: vk::Extent2D) -> Self {
+        Self {
+            extent,
+            skyline: vec![(0, 0, extent.width)],
+        }
+    }
+
+    /// Get the y coordinate a rectangle of the given width would be placed at when starting at segment `index`.
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.skyline[index].0;
+        if x + width > self.extent.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut remaining = width as i64;
+        for &(_, segment_y, segment_width) in &self.skyline[index..] {
+            if remaining <= 0 {
+                break;
+            }
+            y = y.max(segment_y);
+            remaining -= segment_width as i64;
+        }
+        (y + height <= self.extent.height).then_some(y)
+    }
+
+    /// Find a place for a rectangle of the given extent and mark it as used.
+    /// Returns the rectangle's offset, or `None` if it does not fit.
+    pub fn pack(&mut self, extent: vk::Extent2D) -> Option<vk::Offset2D> {
+        let vk::Extent2D { width, height } = extent;
+        let (index, y) = (0..self.skyline.len())
+            .filter_map(|index| self.fit(index, width, height).map(|y| (index, y)))
+            .min_by_key(|&(index, y)| (y + height, self.skyline[index].0))?;
+        let x = self.skyline[index].0;
+
+        // Replace the covered part of the skyline with the rectangle's top edge.
+        self.skyline.insert(index, (x, y + height, width));
+        let end = x + width;
+        let next = index + 1;
+        while next < self.skyline.len() {
+            let (segment_x, segment_y, segment_width) = self.skyline[next];
+            if segment_x >= end {
+                break;
+            }
+            if segment_x + segment_width <= end {
+                self.skyline.remove(next);
+            } else {
+                self.skyline[next] = (end, segment_y, segment_x + segment_width - end);
+                break;
+            }
+        }
+        // Merge neighbouring segments at the same height.
+        self.skyline.dedup_by(|right, left| {
+            if left.1 == right.1 {
+                left.2 += right.2;
+                true
+            } else {
+                false
+            }
+        });
+
+        Some(vk::Offset2D {
+            x: x as i32,
+            y: y as i32,
+        })
+    }
+}
+
+/// The place of an image in a `TextureAtlas`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AtlasRect {
+    /// The page, i.e. the array layer of the atlas texture.
+    pub page: u32,
+    /// Position and size of the image in texels, excluding padding.
+    pub offset: vk::Offset2D,
+    pub extent: vk::Extent2D,
+    /// Normalized texture coordinates of the image's corners.
+    pub uv_min: Vector2<f32>,
+    pub uv_max: Vector2<f32>,
+}
+
+/// A texture atlas packing RGBA images into the pages (array layers) of a 2D array texture.
+/// Images are packed on the host with `insert` and uploaded with partial texture updates by `flush`.
+/// When all pages are full, a new page is added; as the number of layers of an image is fixed,
+/// the atlas texture is then recreated with more layers and the previous pages are copied over.
+///
+/// Every image is surrounded by `padding` texels to avoid bleeding between neighbouring images
+/// when filtering. With `extrude`, the padding repeats the image's edge texels; otherwise, it is transparent.
+pub struct TextureAtlas {
+    pub texture: Texture,
+    pub view: vk::ImageView,
+    pub memory: vk::DeviceMemory,
+    pub padding: u32,
+    pub extrude: bool,
+    pages: Vec<SkylinePacker>,
+    upload_data: Vec<u8>,
+    upload_regions: Vec<TextureRegion>,
+}
+
+impl TextureAtlas {
+    /// Create a new atlas with a single empty page of the given extent. The page is cleared to
+    /// transparent black and transitioned to `dst`, which describes how the atlas is used.
+    /// Fails with `AtlasError::UnsupportedFormat` unless `format` has four 8 bit channels, e.g. `R8G8B8A8_SRGB`.
+    /// # Safety
+    /// Must be called on the thread able to submit command buffers to the given command pool.
+    pub unsafe fn new(
+        vk_engine: &VulkanEngine,
+        command_pool: vk::CommandPool,
+        format: vk::Format,
+        page_extent: vk::Extent2D,
+        padding: u32,
+        extrude: bool,
+        dst: ImageAccess,
+    ) -> Result<Self, AtlasError> {
+        if u8_channel_count(format) != Some(TEXEL_SIZE) {
+            return Err(AtlasError::UnsupportedFormat(format));
+        }
+        let (mut texture, memory, view) = create_pages(vk_engine, format, page_extent, 1, dst)?;
+        vk_engine.one_time_submit(command_pool, |command_buffer| {
+            texture.transition_to(&vk_engine.device, command_buffer, ImageAccess::TRANSFER_DST);
+            clear_pages(&vk_engine.device, command_buffer, &texture, 0, 1);
+            texture.transition_to(&vk_engine.device, command_buffer, dst);
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            memory,
+            padding,
+            extrude,
+            pages: vec![SkylinePacker::new(page_extent)],
+            upload_data: vec![],
+            upload_regions: vec![],
+        })
+    }
+
+    /// Get the number of pages, including pages that will only be created by the next `flush`.
+    pub fn page_count(&self) -> u32 {
+        self.pages.len() as u32
+    }
+
+    /// True iff images have been inserted since the last `flush`.
+    pub fn has_pending_uploads(&self) -> bool {
+        !self.upload_regions.is_empty()
+    }
+
+    /// Packs an RGBA image with the given extent into the atlas and returns its place.
+    /// A new page is added if the image doesn't fit into any existing page.
+    /// The image is uploaded by the next `flush`.
+    pub fn insert(&mut self, rgba: &[u8], extent: vk::Extent2D) -> Result<AtlasRect, AtlasError> {
+        if extent.width == 0
+            || extent.height == 0
+            || rgba.len() != extent.width as usize * extent.height as usize * TEXEL_SIZE
+        {
+            return Err(AtlasError::InvalidData);
+        }
+        let padded_extent = vk::Extent2D {
+            width: extent.width + 2 * self.padding,
+            height: extent.height + 2 * self.padding,
+        };
+        let page_extent = self.pages[0].extent;
+
+        let mut packed = self
+            .pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(page, packer)| packer.pack(padded_extent).map(|offset| (page, offset)));
+        if packed.is_none() {
+            let mut packer = SkylinePacker::new(page_extent);
+            let offset = packer.pack(padded_extent).ok_or(AtlasError::TooLarge(
+                padded_extent.width,
+                padded_extent.height,
+            ))?;
+            self.pages.push(packer);
+            packed = Some((self.pages.len() - 1, offset));
+        }
+        let (page, padded_offset) = packed.unwrap();
+
+        self.upload_regions.push(TextureRegion {
+            data_offset: self.upload_data.len() as _,
+            ..TextureRegion::rect(page as u32, padded_offset, padded_extent)
+        });
+        pad_image(
+            rgba,
+            extent,
+            self.padding,
+            self.extrude,
+            &mut self.upload_data,
+        );
+
+        let offset = vk::Offset2D {
+            x: padded_offset.x + self.padding as i32,
+            y: padded_offset.y + self.padding as i32,
+        };
+        let normalize = |x: i32, y: i32| {
+            Vector2::new(
+                x as f32 / page_extent.width as f32,
+                y as f32 / page_extent.height as f32,
+            )
+        };
+        Ok(AtlasRect {
+            page: page as u32,
+            offset,
+            extent,
+            uv_min: normalize(offset.x, offset.y),
+            uv_max: normalize(
+                offset.x + extent.width as i32,
+                offset.y + extent.height as i32,
+            ),
+        })
+    }
+
+    /// Uploads all images inserted since the last flush with a single-use command buffer
+    /// (using VulkanEngine::one_time_submit). If pages have been added, the atlas texture and view
+    /// are recreated first and the previous ones are queued in `cleanup_queue`.
+    /// Returns true if the texture and view have been recreated, in which case descriptors
+    /// referring to the atlas have to be updated.
+    /// `dst` describes how the atlas is used afterwards.
+    /// # Safety
+    /// Must be called on the thread able to submit command buffers to the given command pool.
+    /// The atlas texture must not be in use by the device, except by frames covered by `cleanup_queue`
+    /// if it is recreated.
+    pub unsafe fn flush(
+        &mut self,
+        vk_engine: &VulkanEngine,
+        command_pool: vk::CommandPool,
+        cleanup_queue: &mut CleanupQueue,
+        dst: ImageAccess,
+    ) -> Result<bool, AtlasError> {
+        let recreated = self.page_count() > self.texture.num_array_layers;
+        if recreated {
+            self.grow(vk_engine, command_pool, cleanup_queue, dst)?;
+        }
+
+        if self.has_pending_uploads() {
+            let mut upload_batch = UploadBatch::new();
+            upload_batch.update_texture(
+                &self.upload_data,
+                &mut self.texture,
+                &self.upload_regions,
+                dst,
+            )?;
+            upload_batch.submit(vk_engine, command_pool);
+            self.upload_data.clear();
+            self.upload_regions.clear();
+        }
+
+        Ok(recreated)
+    }
+
+    /// Recreates the atlas texture with one layer per page, copying the previous pages and clearing the new ones.
+    unsafe fn grow(
+        &mut self,
+        vk_engine: &VulkanEngine,
+        command_pool: vk::CommandPool,
+        cleanup_queue: &mut CleanupQueue,
+        dst: ImageAccess,
+    ) -> Result<(), vk::Result> {
+        let page_extent = self.pages[0].extent;
+        let (mut texture, memory, view) = create_pages(
+            vk_engine,
+            self.texture.format,
+            page_extent,
+            self.page_count(),
+            dst,
+        )?;
+
+        let old_page_count = self.texture.num_array_layers;
+        let device = &vk_engine.device;
+        vk_engine.one_time_submit(command_pool, |command_buffer| {
+            let mut transitions = LayoutTransitions::new();
+            let old_range = self.texture.full_range();
+            transitions.add(&mut self.texture, old_range, ImageAccess::TRANSFER_SRC);
+            let new_range = texture.full_range();
+            transitions.add(&mut texture, new_range, ImageAccess::TRANSFER_DST);
+            transitions.record(device, command_buffer);
+
+            let layers = vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: old_page_count,
+            };
+            device.cmd_copy_image(
+                command_buffer,
+                self.texture.image,
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
+                texture.image,
+                ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[vk::ImageCopy {
+                    src_subresource: layers,
+                    src_offset: vk::Offset3D::default(),
+                    dst_subresource: layers,
+                    dst_offset: vk::Offset3D::default(),
+                    extent: texture.extent,
+                }],
+            );
+            clear_pages(
+                device,
+                command_buffer,
+                &texture,
+                old_page_count,
+                texture.num_array_layers - old_page_count,
+            );
+            if !self.has_pending_uploads() {
+                texture.transition_to(device, command_buffer, dst);
+            }
+        });
+
+        cleanup_queue.queue(std::mem::replace(&mut self.view, view));
+        cleanup_queue.queue(std::mem::replace(&mut self.texture, texture));
+        cleanup_queue.queue(std::mem::replace(&mut self.memory, memory));
+        Ok(())
+    }
+
+    /// Frees the texture, view and memory held by `self`.
+    /// # Safety
+    /// The atlas must not be used anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        device.destroy_image_view(self.view, None);
+        self.texture.destroy(device);
+        device.free_memory(self.memory, None);
+    }
+}
+
+/// Creates an atlas texture with `page_count` layers in dedicated device-local memory and a 2D array view of it.
+unsafe fn create_pages(
+    vk_engine: &VulkanEngine,
+    format: vk::Format,
+    page_extent: vk::Extent2D,
+    page_count: u32,
+    dst: ImageAccess,
+) -> Result<(Texture, vk::DeviceMemory, vk::ImageView), vk::Result> {
+    let mut texture = Texture::new(
+        vk_engine,
+        format,
+        vk::ImageType::TYPE_2D,
+        vk::Extent3D {
+            width: page_extent.width,
+            height: page_extent.height,
+            depth: 1,
+        },
+        page_count,
+        dst.required_usage() | vk::ImageUsageFlags::TRANSFER_SRC,
+    )?;
+
+    let memory_req = texture.get_memory_requirements(&vk_engine.device);
+    let memory_type_index = find_memory_type_index(
+        &memory_req,
+        &vk_engine.device_memory_properties,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    );
+    let memory = match vk_engine.try_allocate_memory(memory_req, memory_type_index) {
+        Ok(memory) => memory,
+        Err(err) => {
+            texture.destroy(&vk_engine.device);
+            return Err(err);
+        }
+    };
+
+    let view = texture.bind_memory(vk_engine, memory, 0).and_then(|_| {
+        texture.create_view(
+            &vk_engine.device,
+            vk::ImageViewType::TYPE_2D_ARRAY,
+            texture.full_range(),
+        )
+    });
+    match view {
+        Ok(view) => Ok((texture, memory, view)),
+        Err(err) => {
+            texture.destroy(&vk_engine.device);
+            vk_engine.device.free_memory(memory, None);
+            Err(err)
+        }
+    }
+}
+
+/// Records a clear of the given pages to transparent black. The pages have to be in `TRANSFER_DST_OPTIMAL` layout.
+unsafe fn clear_pages(
+    device: &Device,
+    command_buffer: vk::CommandBuffer,
+    texture: &Texture,
+    base_page: u32,
+    page_count: u32,
+) {
+    let range = TextureRange {
+        base_array_layer: base_page,
+        layer_count: page_count,
+        ..texture.full_range()
+    };
+    device.cmd_clear_color_image(
+        command_buffer,
+        texture.image,
+        ImageLayout::TRANSFER_DST_OPTIMAL,
+        &vk::ClearColorValue::default(),
+        &[vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: range.base_mip_level,
+            level_count: range.level_count,
+            base_array_layer: range.base_array_layer,
+            layer_count: range.layer_count,
+        }],
+    );
+}
+
+/// Appends the RGBA image surrounded by `padding` texels to `dst`.
+/// The padding repeats the nearest edge texel if `extrude` is set and is transparent otherwise.
+fn pad_image(rgba: &[u8], extent: vk::Extent2D, padding: u32, extrude: bool, dst: &mut Vec<u8>) {
+    let (width, height) = (extent.width as i64, extent.height as i64);
+    let padding = padding as i64;
+    for y in -padding..height + padding {
+        for x in -padding..width + padding {
+            let inside = (0..width).contains(&x) && (0..height).contains(&y);
+            if inside || extrude {
+                let (x, y) = (x.clamp(0, width - 1), y.clamp(0, height - 1));
+                let start = (y * width + x) as usize * TEXEL_SIZE;
+                dst.extend_from_slice(&rgba[start..start + TEXEL_SIZE]);
+            } else {
+                dst.extend_from_slice(&[0; TEXEL_SIZE]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skyline_packing() {
+        let extent = |width, height| vk::Extent2D { width, height };
+        let mut packer = SkylinePacker::new(extent(8, 8));
+        let offset = |x, y| Some(vk::Offset2D { x, y });
+        assert_eq!(packer.pack(extent(4, 4)), offset(0, 0));
+        assert_eq!(packer.pack(extent(4, 2)), offset(4, 0));
+        assert_eq!(packer.pack(extent(4, 2)), offset(4, 2));
+        // The skyline is flat again at height 4.
+        assert_eq!(packer.skyline, vec![(0, 4, 8)]);
+        assert_eq!(packer.pack(extent(8, 4)), offset(0, 4));
+        assert_eq!(packer.pack(extent(1, 1)), None);
+    }
+
+    #[test]
+    fn padding_and_extrusion() {
+        let rgba = [1, 1, 1, 1, 2, 2, 2, 2];
+        let extent = vk::Extent2D {
+            width: 2,
+            height: 1,
+        };
+        let mut padded = vec![];
+        pad_image(&rgba, extent, 1, true, &mut padded);
+        let texels = padded.chunks(4).map(|texel| texel[0]).collect::<Vec<_>>();
+        assert_eq!(texels, [1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2]);
+
+        let mut padded = vec![];
+        pad_image(&rgba, extent, 1, false, &mut padded);
+        let texels = padded.chunks(4).map(|texel| texel[0]).collect::<Vec<_>>();
+        assert_eq!(texels, [0, 0, 0, 0, 0, 1, 2, 0, 0, 0, 0, 0]);
+    }
+}
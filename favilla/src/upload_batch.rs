@@ -0,0 +1,455 @@
+use crate::allocator::get_aligned_offset;
+use crate::buffer::{BufferCopyError, StagingBufferWithDedicatedAllocation, VulkanBuffer};
+use crate::format::format_info;
+use crate::texture::layout_state::LayoutTransitions;
+use crate::texture::{
+    copy_buffer_to_image_regions, ImageAccess, Texture, TextureCopyError, TextureRegion,
+};
+use crate::vk_engine::VulkanEngine;
+use ash::{vk, Device};
+use std::mem::MaybeUninit;
+
+/// Offset alignment of buffer uploads and of texture uploads with formats unknown to `format_info`
+/// in the staging memory. Texture uploads of known formats are aligned to `FormatInfo::copy_alignment`,
+/// which also covers texel sizes that don't divide 16, e.g. the 3 bytes of `R8G8B8_UNORM`.
+const UPLOAD_ALIGNMENT: u64 = 16;
+
+/// Copies to an image must start at a multiple of both its texel block size and 4.
+fn texture_upload_alignment(texture: &Texture) -> u64 {
+    format_info(texture.format).map_or(UPLOAD_ALIGNMENT, |info| info.copy_alignment())
+}
+
+/// True iff both copies write to the same texels of the same aspect, mip level and array layer.
+fn copies_overlap(a: &vk::BufferImageCopy, b: &vk::BufferImageCopy) -> bool {
+    let (a_subresource, b_subresource) = (a.image_subresource, b.image_subresource);
+    let ranges_overlap = |a_start: i64, a_len: u32, b_start: i64, b_len: u32| {
+        a_start < b_start + b_len as i64 && b_start < a_start + a_len as i64
+    };
+    a_subresource.aspect_mask == b_subresource.aspect_mask
+        && a_subresource.mip_level == b_subresource.mip_level
+        && ranges_overlap(
+            a_subresource.base_array_layer as i64,
+            a_subresource.layer_count,
+            b_subresource.base_array_layer as i64,
+            b_subresource.layer_count,
+        )
+        && ranges_overlap(
+            a.image_offset.x as i64,
+            a.image_extent.width,
+            b.image_offset.x as i64,
+            b.image_extent.width,
+        )
+        && ranges_overlap(
+            a.image_offset.y as i64,
+            a.image_extent.height,
+            b.image_offset.y as i64,
+            b.image_extent.height,
+        )
+        && ranges_overlap(
+            a.image_offset.z as i64,
+            a.image_extent.depth,
+            b.image_offset.z as i64,
+            b.image_extent.depth,
+        )
+}
+
+/// Collects many buffer and texture uploads and records them into a single command buffer
+/// with one batched barrier before and one batched barrier after all copies.
+/// The batch manages its own staging memory: data is gathered on the host and written to
+/// a single staging buffer when the batch is submitted.
+#[derive(Default)]
+pub struct UploadBatch {
+    data: Vec<MaybeUninit<u8>>,
+    buffer_copies: Vec<PendingBufferCopy>,
+    texture_copies: Vec<PendingTextureCopy>,
+}
+
+struct PendingBufferCopy {
+    buffer: vk::Buffer,
+    copy: vk::BufferCopy,
+    dst_stage_mask: vk::PipelineStageFlags,
+    dst_access_mask: vk::AccessFlags,
+}
+
+struct PendingTextureCopy {
+    image: vk::Image,
+    regions: Vec<vk::BufferImageCopy>,
+    pre_transitions: LayoutTransitions,
+    post_transitions: LayoutTransitions,
+    dst: ImageAccess,
+}
+
+impl UploadBatch {
+    /// Create a new, empty upload batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// True iff no uploads have been added.
+    pub fn is_empty(&self) -> bool {
+        self.buffer_copies.is_empty() && self.texture_copies.is_empty()
+    }
+
+    /// Get the size of the staging memory required for all uploads added so far.
+    pub fn staging_size(&self) -> vk::DeviceSize {
+        self.data.len() as _
+    }
+
+    fn push_data<T: Copy>(&mut self, data: &[T], alignment: u64) -> vk::DeviceSize {
+        let offset = get_aligned_offset(self.data.len() as _, alignment);
+        let size = std::mem::size_of_val(data);
+        self.data
+            .resize(offset as usize + size, MaybeUninit::uninit());
+        // SAFETY: the destination range has just been allocated and does not overlap `data`.
+        // Copying into `MaybeUninit<u8>` is fine even if `T` contains padding bytes.
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                data.as_ptr() as *const MaybeUninit<u8>,
+                self.data.as_mut_ptr().add(offset as usize),
+                size,
+            );
+        }
+        offset
+    }
+
+    /// Add an upload of `data` to `dst`, starting at the element with index `dst_offset_in_element_count`.
+    /// `dst_stage_mask` and `dst_access_mask` describe the first use of the buffer after the upload.
+    pub fn upload_buffer<T: Copy>(
+        &mut self,
+        data: &[T],
+        dst: &VulkanBuffer<T>,
+        dst_offset_in_element_count: u64,
+        dst_stage_mask: vk::PipelineStageFlags,
+        dst_access_mask: vk::AccessFlags,
+    ) -> Result<(), BufferCopyError> {
+        if dst_offset_in_element_count + data.len() as u64 > dst.length {
+            return Err(BufferCopyError::SpecifiedLengthExceedsBounds);
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let src_offset = self.push_data(data, UPLOAD_ALIGNMENT);
+        let element_size = std::mem::size_of::<T>() as u64;
+        self.buffer_copies.push(PendingBufferCopy {
+            buffer: dst.buffer,
+            copy: vk::BufferCopy {
+                src_offset,
+                dst_offset: dst_offset_in_element_count * element_size,
+                size: data.len() as u64 * element_size,
+            },
+            dst_stage_mask,
+            dst_access_mask,
+        });
+        Ok(())
+    }
+
+    /// Add an upload of `data` to all array layers of `texture`.
+    /// The data has to be tightly packed, layer after layer.
+    /// The texture's previous content is discarded.
+    /// Only mip level 0 is written; textures with a mip chain should use `Texture::upload_with_mipmaps` instead.
+    /// `dst` describes the first use of the texture after the upload.
+    /// The texture's tracked layout state is updated immediately, so the batch has to be recorded
+    /// before any other transition of the texture.
+    /// A texture can be added to a batch several times; its copies are merged and it is transitioned once
+    /// before and after all of them. If `dst` differs in layout from an earlier addition, the later layout is used.
+    /// Fails without adding anything if `data` is too small for mip level 0 of all layers, or if the texture
+    /// has already been added to the batch with an overlapping region.
+    pub fn upload_texture<T: Copy>(
+        &mut self,
+        data: &[T],
+        texture: &mut Texture,
+        dst: ImageAccess,
+    ) -> Result<(), TextureCopyError> {
+        let region = TextureRegion::mip_level(texture, 0);
+        let copy =
+            texture.buffer_image_copy(&region, 0, std::mem::size_of_val(data) as vk::DeviceSize)?;
+        self.add_texture_copies(data, texture, vec![copy], true, dst)
+    }
+
+    /// Add an update of the given regions of `texture`, e.g. sub-rectangles, single array layers
+    /// or single mip levels. Each region's data is read from `data` at the region's data offset;
+    /// all regions are copied with a single copy command. The rest of the texture's content is kept.
+    /// The texture is transitioned from its tracked layout state, which is updated immediately (see `upload_texture`).
+    /// `dst` describes the first use of the texture after the update.
+    /// Like `upload_texture`, fails if the texture has already been added with an overlapping region.
+    pub fn update_texture<T: Copy>(
+        &mut self,
+        data: &[T],
+        texture: &mut Texture,
+        regions: &[TextureRegion],
+        dst: ImageAccess,
+    ) -> Result<(), TextureCopyError> {
+        let data_size = std::mem::size_of_val(data) as vk::DeviceSize;
+        // Validate all regions before adding any data.
+        let copies = regions
+            .iter()
+            .map(|region| texture.buffer_image_copy(region, 0, data_size))
+            .collect::<Result<Vec<_>, _>>()?;
+        if copies.is_empty() {
+            return Ok(());
+        }
+        self.add_texture_copies(data, texture, copies, false, dst)
+    }
+
+    /// Adds `copies` of `data` to `texture`, merging them into the texture's pending copy if the texture
+    /// has already been added to the batch, so that each texture gets a single pre and post transition.
+    fn add_texture_copies<T: Copy>(
+        &mut self,
+        data: &[T],
+        texture: &mut Texture,
+        mut copies: Vec<vk::BufferImageCopy>,
+        discard: bool,
+        dst: ImageAccess,
+    ) -> Result<(), TextureCopyError> {
+        let pending_index = self
+            .texture_copies
+            .iter()
+            .position(|pending| pending.image == texture.image);
+        if let Some(index) = pending_index {
+            let pending = &self.texture_copies[index];
+            if copies.iter().any(|copy| {
+                pending
+                    .regions
+                    .iter()
+                    .any(|other| copies_overlap(copy, other))
+            }) {
+                return Err(TextureCopyError::OverlappingRegions);
+            }
+        }
+
+        let buffer_offset = self.push_data(data, texture_upload_alignment(texture));
+        for copy in &mut copies {
+            copy.buffer_offset += buffer_offset;
+        }
+        let range = texture.full_range();
+        match pending_index {
+            Some(index) => {
+                // The texture stays in `TRANSFER_DST` until all of the batch's copies are done,
+                // so only the transition afterwards changes; it has to cover both uses.
+                let pending = &mut self.texture_copies[index];
+                let dst = if pending.dst.layout == dst.layout {
+                    ImageAccess {
+                        stage_mask: pending.dst.stage_mask | dst.stage_mask,
+                        access_mask: pending.dst.access_mask | dst.access_mask,
+                        layout: dst.layout,
+                    }
+                } else {
+                    dst
+                };
+                texture.layout_state.set(range, ImageAccess::TRANSFER_DST);
+                pending.post_transitions = LayoutTransitions::new();
+                pending.post_transitions.add(texture, range, dst);
+                pending.regions.extend(copies);
+                pending.dst = dst;
+            }
+            None => {
+                if discard {
+                    texture.layout_state.discard(range);
+                }
+                let mut pre_transitions = LayoutTransitions::new();
+                pre_transitions.add(texture, range, ImageAccess::TRANSFER_DST);
+                let mut post_transitions = LayoutTransitions::new();
+                post_transitions.add(texture, range, dst);
+                self.texture_copies.push(PendingTextureCopy {
+                    image: texture.image,
+                    regions: copies,
+                    pre_transitions,
+                    post_transitions,
+                    dst,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Records all uploads into `command_buffer`, using `staging_buffer` as the source.
+    /// The staging buffer has to hold the batch's data at offset 0 (see `write_staging_data`).
+    /// # Safety
+    /// The staging buffer must be at least `staging_size()` bytes large and have `TRANSFER_SRC` usage.
+    /// The destination buffers and textures must be valid and not in use.
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        command_buffer: vk::CommandBuffer,
+        staging_buffer: vk::Buffer,
+    ) {
+        let mut pre_transitions = LayoutTransitions::new();
+        for copy in &self.texture_copies {
+            pre_transitions.merge(&copy.pre_transitions);
+        }
+        pre_transitions.record(device, command_buffer);
+
+        for copy in &self.buffer_copies {
+            device.cmd_copy_buffer(command_buffer, staging_buffer, copy.buffer, &[copy.copy]);
+        }
+
+        for copy in &self.texture_copies {
+            copy_buffer_to_image_regions(
+                device,
+                command_buffer,
+                staging_buffer,
+                copy.image,
+                &copy.regions,
+            );
+        }
+
+        let mut src_stage_mask = vk::PipelineStageFlags::empty();
+        let mut dst_stage_mask = vk::PipelineStageFlags::empty();
+        let buffer_barriers = self
+            .buffer_copies
+            .iter()
+            .map(|copy| {
+                src_stage_mask |= vk::PipelineStageFlags::TRANSFER;
+                dst_stage_mask |= copy.dst_stage_mask;
+                vk::BufferMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(copy.dst_access_mask)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .buffer(copy.buffer)
+                    .offset(copy.copy.dst_offset)
+                    .size(copy.copy.size)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let mut image_barriers = Vec::new();
+        for copy in &self.texture_copies {
+            src_stage_mask |= copy.post_transitions.src_stage_mask;
+            dst_stage_mask |= copy.post_transitions.dst_stage_mask;
+            image_barriers.extend_from_slice(&copy.post_transitions.barriers);
+        }
+
+        if !dst_stage_mask.is_empty() {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                src_stage_mask,
+                dst_stage_mask,
+                vk::DependencyFlags::empty(),
+                &[],
+                &buffer_barriers,
+                &image_barriers,
+            );
+        }
+    }
+
+    /// Writes the batch's data to mapped staging memory.
+    /// # Safety
+    /// `ptr` must point to at least `staging_size()` writable bytes.
+    pub unsafe fn write_staging_data(&self, ptr: *mut u8) {
+        std::ptr::copy_nonoverlapping(self.data.as_ptr() as *const u8, ptr, self.data.len());
+    }
+
+    /// Allocates a staging buffer, records all uploads into a single-use command buffer
+    /// (using VulkanEngine::one_time_submit) and waits for them to finish.
+    /// # Safety
+    /// Must be called on the thread able to submit command buffers to the given command pool.
+    /// The destination buffers and textures must be valid and not in use.
+    pub unsafe fn submit(self, vk_engine: &VulkanEngine, command_pool: vk::CommandPool) {
+        if self.is_empty() {
+            return;
+        }
+
+        let mut staging_buffer = StagingBufferWithDedicatedAllocation::<u8>::allocate(
+            vk_engine,
+            self.staging_size(),
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::SharingMode::EXCLUSIVE,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        self.write_staging_data(staging_buffer.buffer.buffer_ptr);
+
+        vk_engine.one_time_submit(command_pool, |command_buffer| {
+            self.record(
+                &vk_engine.device,
+                command_buffer,
+                staging_buffer.buffer.buffer.buffer,
+            );
+        });
+
+        staging_buffer.destroy(&vk_engine.device);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::layout_state::LayoutState;
+    use ash::vk::Handle;
+
+    #[test]
+    fn repeated_texture_uploads_are_merged() {
+        let mut texture = Texture {
+            format: vk::Format::R8G8B8A8_UNORM,
+            image: vk::Image::from_raw(1),
+            image_type: vk::ImageType::TYPE_2D,
+            flags: vk::ImageCreateFlags::empty(),
+            extent: vk::Extent3D {
+                width: 8,
+                height: 8,
+                depth: 1,
+            },
+            num_array_layers: 1,
+            mip_levels: 1,
+            layout_state: LayoutState::new(1, 1),
+        };
+        let extent = vk::Extent2D {
+            width: 4,
+            height: 4,
+        };
+        let glyph = |x| TextureRegion::rect(0, vk::Offset2D { x, y: 0 }, extent);
+        let data = [0u8; 64];
+
+        let mut batch = UploadBatch::new();
+        batch
+            .update_texture(
+                &data,
+                &mut texture,
+                &[glyph(0)],
+                ImageAccess::FRAGMENT_SHADER_READ,
+            )
+            .unwrap();
+        batch
+            .update_texture(
+                &data,
+                &mut texture,
+                &[glyph(4)],
+                ImageAccess::VERTEX_SHADER_READ,
+            )
+            .unwrap();
+        let staging_size = batch.staging_size();
+        assert!(matches!(
+            batch.update_texture(
+                &data,
+                &mut texture,
+                &[glyph(2)],
+                ImageAccess::FRAGMENT_SHADER_READ
+            ),
+            Err(TextureCopyError::OverlappingRegions)
+        ));
+        assert_eq!(batch.staging_size(), staging_size);
+
+        assert_eq!(batch.texture_copies.len(), 1);
+        let pending = &batch.texture_copies[0];
+        assert_eq!(pending.regions.len(), 2);
+        assert_eq!(pending.regions[1].buffer_offset, 64);
+        assert_eq!(pending.pre_transitions.barriers.len(), 1);
+        assert_eq!(
+            pending.pre_transitions.barriers[0].new_layout,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL
+        );
+        assert_eq!(pending.post_transitions.barriers.len(), 1);
+        assert_eq!(
+            pending.post_transitions.barriers[0].old_layout,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL
+        );
+        assert_eq!(
+            pending.post_transitions.dst_stage_mask,
+            vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::VERTEX_SHADER
+        );
+        assert_eq!(
+            texture.layout_state.get(0, 0).stage_mask,
+            vk::PipelineStageFlags::FRAGMENT_SHADER | vk::PipelineStageFlags::VERTEX_SHADER
+        );
+    }
+}
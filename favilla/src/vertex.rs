@@ -0,0 +1,66 @@
+use ash::vk;
+use cgmath::{Point2, Point3, Vector2, Vector3, Vector4};
+
+#[cfg(feature = "derive")]
+pub use favilla_derive::Vertex;
+
+/// Describes the vertex input layout of a vertex type for use in a graphics pipeline.
+/// Can be derived with `#[derive(Vertex)]` (requires the `derive` feature).
+pub trait VertexInput {
+    /// The binding descriptions for this vertex type.
+    fn binding_descriptions() -> Vec<vk::VertexInputBindingDescription>;
+
+    /// The attribute descriptions for all fields of this vertex type.
+    fn attribute_descriptions() -> Vec<vk::VertexInputAttributeDescription>;
+}
+
+/// Types that can be used as fields of a vertex type, i.e. as a single vertex attribute.
+pub trait VertexAttribute {
+    /// The format of the attribute.
+    const FORMAT: vk::Format;
+}
+
+/// Four 8-bit unsigned components that are normalized to [0, 1] when read by a shader,
+/// e.g. for packed vertex colours.
+#[repr(transparent)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Unorm8x4(pub [u8; 4]);
+
+macro_rules! impl_vertex_attribute {
+    ($($ty:ty => $format:ident),* $(,)?) => {
+        $(
+            impl VertexAttribute for $ty {
+                const FORMAT: vk::Format = vk::Format::$format;
+            }
+        )*
+    };
+}
+
+impl_vertex_attribute! {
+    f32 => R32_SFLOAT,
+    [f32; 2] => R32G32_SFLOAT,
+    [f32; 3] => R32G32B32_SFLOAT,
+    [f32; 4] => R32G32B32A32_SFLOAT,
+    u32 => R32_UINT,
+    [u32; 2] => R32G32_UINT,
+    [u32; 3] => R32G32B32_UINT,
+    [u32; 4] => R32G32B32A32_UINT,
+    i32 => R32_SINT,
+    [i32; 2] => R32G32_SINT,
+    [i32; 3] => R32G32B32_SINT,
+    [i32; 4] => R32G32B32A32_SINT,
+    [u16; 2] => R16G16_UINT,
+    [u16; 4] => R16G16B16A16_UINT,
+    [u8; 4] => R8G8B8A8_UINT,
+    Unorm8x4 => R8G8B8A8_UNORM,
+    Vector2<f32> => R32G32_SFLOAT,
+    Vector3<f32> => R32G32B32_SFLOAT,
+    Vector4<f32> => R32G32B32A32_SFLOAT,
+    Point2<f32> => R32G32_SFLOAT,
+    Point3<f32> => R32G32B32_SFLOAT,
+}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use ash::vk;
+}
@@ -115,14 +115,51 @@ impl VulkanEngine {
         memory_req: vk::MemoryRequirements,
         memory_type_index: u32,
     ) -> Result<vk::DeviceMemory, vk::Result> {
-        let buffer_allocate_info = vk::MemoryAllocateInfo {
-            allocation_size: memory_req.size,
+        self.try_allocate_memory_with_flags(
+            memory_req,
             memory_type_index,
-            ..Default::default()
-        };
+            vk::MemoryAllocateFlags::empty(),
+        )
+    }
+
+    /// Perform a new memory allocation with the given allocation flags,
+    /// e.g. `DEVICE_ADDRESS` for memory bound to buffers with `SHADER_DEVICE_ADDRESS` usage.
+    /// # Safety
+    /// Must not exceed memory limitations. The features required by the flags must be enabled.
+    pub unsafe fn try_allocate_memory_with_flags(
+        &self,
+        memory_req: vk::MemoryRequirements,
+        memory_type_index: u32,
+        allocate_flags: vk::MemoryAllocateFlags,
+    ) -> Result<vk::DeviceMemory, vk::Result> {
+        let mut allocate_flags_info = vk::MemoryAllocateFlagsInfo::builder().flags(allocate_flags);
+        let mut buffer_allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(memory_req.size)
+            .memory_type_index(memory_type_index);
+        if !allocate_flags.is_empty() {
+            buffer_allocate_info = buffer_allocate_info.push_next(&mut allocate_flags_info);
+        }
         self.device.allocate_memory(&buffer_allocate_info, None)
     }
 
+    /// Perform a new memory allocation dedicated to `image` (`VkMemoryDedicatedAllocateInfo`).
+    /// The image has to be bound to the memory at offset 0 and must not be bound to other memory.
+    /// # Safety
+    /// Must not exceed memory limitations. Requires Vulkan 1.1.
+    pub unsafe fn try_allocate_dedicated_image_memory(
+        &self,
+        memory_req: vk::MemoryRequirements,
+        memory_type_index: u32,
+        image: vk::Image,
+    ) -> Result<vk::DeviceMemory, vk::Result> {
+        let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::builder().image(image);
+        let image_allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(memory_req.size)
+            .memory_type_index(memory_type_index)
+            .push_next(&mut dedicated_info);
+        self.device.allocate_memory(&image_allocate_info, None)
+    }
+
     /// Advance to the next frame.
     /// This has to be called every frame in order for the synchronisation support to work properly.
     pub fn advance_frame(&mut self) {